@@ -6,16 +6,25 @@ extern crate itertools_num;
 extern crate num;
 extern crate printpdf;
 
+use self::chrono::NaiveDateTime;
 use self::enumflags2::BitFlags;
-use crate::data::create_limits_file;
+use crate::data::{create_limits_file, parameter_names, ColumnKind, DatFormat, UnitsRow};
+use crate::derive::Derive;
+use crate::dialect::{encoding_for_label, open_reader, Dialect};
 use crate::group::GroupBy;
+use crate::limits::lint_limits;
+use crate::numbers::{Quantile, Sentinel};
 use crate::pdf::Paper;
+use crate::qdas::is_qdas;
 use crate::qtable::{default_columns, Align, Column, Filter, Mark, Order, Show};
 use crate::sample;
 use crate::sample::write_sample_file;
-use csv::Reader;
+use crate::select::{ParamPattern, Predicate};
+use crate::stdf::is_stdf;
+use crate::time::{parse_time, parse_time_to, LimitsDate};
+use crate::workbook::{file_path, is_workbook, sheet_names, split_sheet, SHEET_SEPARATOR};
+use csv::{Reader, StringRecord};
 use std::collections::HashMap;
-use std::fs::File;
 use std::path::Path;
 
 #[derive(Debug, Clone, PartialEq)]
@@ -35,13 +44,48 @@ pub struct QTableProps {
     pub nanstring: String,
     pub sig_digits: usize,
     pub filter: Filter,
+    /// definition of median, percentiles and iqr (Hyndman and Fan type 1 to 9)
+    pub quantile: Quantile,
     pub float_limit: f64,
     pub spec_yield_limit: f64,
     pub ctrl_yield_limit: f64,
     pub cpk_limit: f64,
+    /// percentage of the values used, below which a parameter is marked
+    pub completeness_limit: f64,
     pub group_by: Vec<GroupBy>,
     pub longgroupnames: bool,
+    /// print the limits row matched by each parameter
+    pub limits_report: bool,
+    /// date the limits are valid for
+    pub limits_date: LimitsDate,
+    /// how the limits command derives limits
+    pub derive: Derive,
+    /// the limits command keeps LSL, TGT, USL of the limits files and derives only control limits
+    pub derive_keep_spec: bool,
+    /// the limits command adds a keyed row for every group of groupby
+    pub derive_groups: bool,
+    /// control limits are proposed from the data and compared with the applied ones
+    pub propose: bool,
     pub histogram_bins: usize,
+    pub datformat: DatFormat,
+    pub long_parameter: String,
+    pub long_value: String,
+    pub long_keys: Vec<String>,
+    pub dialect: Dialect,
+    pub streaming: bool,
+    pub select: Vec<Predicate>,
+    /// parameters shown, all if empty
+    pub include: Vec<ParamPattern>,
+    /// parameters not shown
+    pub exclude: Vec<ParamPattern>,
+    pub time_column: String,
+    pub time_format: String,
+    pub time_from: Option<NaiveDateTime>,
+    pub time_to: Option<NaiveDateTime>,
+    pub time_last_days: Option<f64>,
+    pub column_kinds: Vec<(String, ColumnKind)>,
+    pub units_row: UnitsRow,
+    pub sentinels: Vec<Sentinel>,
 }
 
 pub fn default_props() -> QTableProps {
@@ -57,17 +101,42 @@ pub fn default_props() -> QTableProps {
         fontsize: 7,
         align: Align::SpecLimits,
         show: Show::SpecLimits | Show::ControlLimits | Show::Targets,
-        mark: Mark::SpecYield | Mark::ControlYield | Mark::Cpk,
+        mark: Mark::SpecYield | Mark::ControlYield | Mark::Cpk | Mark::Completeness,
         nanstring: "".to_string(),
         sig_digits: 4,
         filter: Filter::None,
-        float_limit: std::f64::MAX,
+        quantile: Quantile::default(),
+        float_limit: f64::MAX,
         spec_yield_limit: 100.0,
         ctrl_yield_limit: 100.0,
         cpk_limit: 1.67,
+        completeness_limit: 90.0,
         group_by: vec![],
         longgroupnames: false,
+        limits_report: false,
+        limits_date: LimitsDate::Now,
+        derive: Derive::default(),
+        derive_keep_spec: false,
+        derive_groups: false,
+        propose: false,
         histogram_bins: 11,
+        datformat: DatFormat::Wide,
+        long_parameter: "PARAMETER".to_string(),
+        long_value: "VALUE".to_string(),
+        long_keys: vec![],
+        dialect: Dialect::default(),
+        streaming: false,
+        select: vec![],
+        include: vec![],
+        exclude: vec![],
+        time_column: "".to_string(),
+        time_format: "".to_string(),
+        time_from: None,
+        time_to: None,
+        time_last_days: None,
+        column_kinds: vec![],
+        units_row: UnitsRow::Auto,
+        sentinels: vec![],
     }
 }
 
@@ -86,16 +155,111 @@ pub fn info(err: String) {
     }
 }
 
+/// role of an input file found by its header row
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum FileRole {
+    Data,
+    Limits,
+    Config,
+    Empty,
+}
+
+/// find the role of a CSV file or workbook sheet by sniffing its header row
+fn file_role(filepath: &str) -> Result<FileRole, String> {
+    if is_stdf(filepath) || is_qdas(filepath) {
+        return Ok(FileRole::Data);
+    }
+
+    let (mut rdr, _) = match open_reader(filepath, &Dialect::default()) {
+        Err(e) => {
+            return Err(format!("could not open file '{}': '{}'.", filepath, e));
+        }
+        Ok(rdr) => rdr,
+    };
+
+    let mut is_use = false;
+    let mut is_par = false;
+    let mut is_lim = false;
+    let mut is_opt = false;
+    let mut is_val = false;
+
+    let headers = match rdr.headers() {
+        Ok(record) => record,
+        Err(e) => {
+            return Err(format!("could not read CSV file '{}': '{}'.", filepath, e));
+        }
+    };
+
+    if headers.iter().all(|h| h.trim().is_empty()) {
+        return Ok(FileRole::Empty);
+    }
+
+    for h in headers.iter() {
+        match h.to_uppercase().as_str() {
+            "USE" => is_use = true,
+            "PAR" => is_par = true,
+            "LSL" => is_lim = true,
+            "USL" => is_lim = true,
+            "LCL" => is_lim = true,
+            "UCL" => is_lim = true,
+            "TGT" => is_lim = true,
+            "OPT" => is_opt = true,
+            "VAL" => is_val = true,
+            _ => (),
+        }
+    }
+
+    if is_use && is_par && is_lim {
+        Ok(FileRole::Limits)
+    } else if is_use && is_opt && is_val {
+        Ok(FileRole::Config)
+    } else {
+        Ok(FileRole::Data)
+    }
+}
+
+/// file names matching a glob pattern like `daily_*.csv`, sorted
+fn expand_glob(pattern: &str) -> Result<Vec<String>, String> {
+    let paths = match glob::glob(pattern) {
+        Err(e) => return Err(format!("invalid file pattern '{}': {}.", pattern, e)),
+        Ok(paths) => paths,
+    };
+    let mut files: Vec<String> = paths
+        .filter_map(|p| p.ok())
+        .filter(|p| p.is_file())
+        .map(|p| p.to_string_lossy().to_string())
+        .collect();
+    if files.is_empty() {
+        return Err(format!("no file matches '{}'.", pattern));
+    }
+    files.sort();
+    Ok(files)
+}
+
 pub fn check_infiles(
     infiles: Vec<String>,
     prognam: &str,
-) -> Result<(String, String, String), String> {
-    let mut datpath = "";
-    let mut limpath = "";
-    let mut cfgpath = "";
+) -> Result<(Vec<String>, Vec<String>, String), String> {
+    let mut datpaths: Vec<String> = vec![];
+    let mut limpaths: Vec<String> = vec![];
+    let mut cfgpath = String::new();
     let mut want_limits_file = false;
+    let mut want_lint = false;
+    // path and significant digits of the limits file written by the limits command
+    let mut outpath: Option<String> = None;
+    let mut sigdigits: usize = 5;
+
+    let mut expanded = vec![];
+    for filepath in infiles.into_iter() {
+        // an existing file like 'lot[1].csv' is no pattern
+        let exists = Path::new(file_path(&filepath)).exists();
+        match !exists && filepath.contains(['*', '?', '[']) {
+            true => expanded.extend(expand_glob(&filepath)?),
+            false => expanded.push(filepath),
+        }
+    }
 
-    for filepath in infiles.iter() {
+    for filepath in expanded.iter() {
         if filepath == "samples" {
             write_sample_file(".", "dat")?;
             write_sample_file(".", "lim")?;
@@ -106,109 +270,147 @@ pub fn check_infiles(
             want_limits_file = true;
             continue;
         }
+        if let Some(out) = filepath.strip_prefix("out=") {
+            outpath = Some(out.to_string());
+            continue;
+        }
+        if let Some(digits) = filepath.strip_prefix("sigdigits=") {
+            sigdigits = match digits.parse::<usize>() {
+                Ok(v) if v > 0 => v,
+                _ => {
+                    return Err(format!(
+                        "'{}' is invalid, sigdigits= must be a number greater than 0.",
+                        filepath
+                    ))
+                }
+            };
+            continue;
+        }
+        if filepath == "lint" {
+            want_lint = true;
+            continue;
+        }
         if filepath == "help" {
             sample::help(prognam);
             std::process::exit(0);
         }
 
-        let path = Path::new(filepath);
+        let (file, sheet) = split_sheet(filepath);
+        let path = Path::new(file);
         if !(path.exists() && path.is_file()) {
-            return Err(format!("file '{}' is not existent.", filepath));
+            return Err(format!("file '{}' is not existent.", file));
         }
 
-        let file = match File::open(&path) {
-            Err(_) => {
-                return Err(format!("CSV file '{}' is not existent.", path.display()));
-            }
-            Ok(file) => file,
+        // every sheet of a workbook given without sheet is checked for its role
+        let whole_workbook = is_workbook(filepath) && sheet.is_none();
+        let candidates: Vec<String> = match whole_workbook {
+            true => match sheet_names(filepath) {
+                Err(e) => return Err(format!("could not open workbook '{}': '{}'.", file, e)),
+                Ok(names) => names
+                    .iter()
+                    .map(|n| format!("{}{}{}", file, SHEET_SEPARATOR, n))
+                    .collect(),
+            },
+            false => vec![filepath.to_string()],
         };
 
-        let mut rdr: Reader<File> = Reader::from_reader(file);
-
-        let mut is_use = false;
-        let mut is_par = false;
-        let mut is_lim = false;
-        let mut is_opt = false;
-        let mut is_val = false;
-
-        let headers = match rdr.headers() {
-            Ok(record) => record,
-            Err(e) => {
-                return Err(format!(
-                    "could not read CSV file '{}': '{}'.",
-                    &path.display(),
-                    e
-                ));
-            }
-        };
-
-        for h in headers.iter() {
-            let v = h.to_uppercase();
-            let w = &v;
-            let u: &str = &w;
-            match u {
-                "USE" => is_use = true,
-                "PAR" => is_par = true,
-                "LSL" => is_lim = true,
-                "USL" => is_lim = true,
-                "LCL" => is_lim = true,
-                "UCL" => is_lim = true,
-                "TGT" => is_lim = true,
-                "OPT" => is_opt = true,
-                "VAL" => is_val = true,
-                _ => (),
-            }
-        }
-
-        if is_use && is_par && is_lim {
-            if limpath == "" {
-                limpath = &filepath;
-            } else {
-                return Err(format!(
-                    "can only read one CSV limit file '{}', limit file '{}' cannot be loaded.",
-                    &limpath, &filepath
-                ));
-            }
-        } else {
-            if is_use && is_opt && is_val {
-                if cfgpath == "" {
-                    cfgpath = &filepath;
-                } else {
-                    return Err(format!("can only read one CSV config file '{}', config file '{}' cannot be loaded.", &limpath, &filepath));
-                }
-            } else {
-                if datpath == "" {
-                    datpath = &filepath;
-                } else {
-                    return Err(format!(
-                        "can only read one CSV data file '{}', data file '{}' cannot be loaded.",
-                        &datpath, &filepath
-                    ));
+        let mut workbook_data: Option<String> = None;
+        for candidate in candidates {
+            match file_role(&candidate)? {
+                FileRole::Empty => continue,
+                // limits files in the order given, later ones override earlier ones field by field
+                FileRole::Limits => {
+                    if limpaths.contains(&candidate) {
+                        println!("limits file '{}' is given more than once, it is read once.", &candidate);
+                    } else {
+                        limpaths.push(candidate);
+                    }
+                }
+                FileRole::Config => {
+                    if cfgpath.is_empty() {
+                        cfgpath = candidate;
+                    } else {
+                        return Err(format!("can only read one CSV config file '{}', config file '{}' cannot be loaded.", &cfgpath, &candidate));
+                    }
                 }
+                FileRole::Data => match &workbook_data {
+                    Some(datpath) => println!(
+                        "sheet '{}' is skipped, data is read from '{}' (use 'book.xlsx{}sheet' to select another sheet).",
+                        &candidate, datpath, SHEET_SEPARATOR
+                    ),
+                    None => {
+                        if whole_workbook {
+                            workbook_data = Some(candidate.clone());
+                        }
+                        if datpaths.contains(&candidate) {
+                            println!("data file '{}' is given more than once, it is read once.", &candidate);
+                        } else {
+                            datpaths.push(candidate);
+                        }
+                    }
+                },
             }
         }
     }
 
-    if datpath == "" {
+    if datpaths.is_empty() {
         return Err(format!(
             "HINT: at least one CSV data file must be provided."
         ));
     }
 
     if want_limits_file {
-        create_limits_file(&datpath.to_string(), 5)?;
+        let (qtableprops, _) = read_config(&cfgpath)?;
+        create_limits_file(
+            &datpaths,
+            &limpaths,
+            outpath.as_deref(),
+            sigdigits,
+            &qtableprops,
+        )?;
         std::process::exit(0);
     }
 
-    Ok((
-        datpath.to_string(),
-        limpath.to_string(),
-        cfgpath.to_string(),
-    ))
+    if want_lint {
+        let issues = lint(&datpaths, &limpaths, &cfgpath)?;
+        std::process::exit(if issues == 0 { 0 } else { 1 });
+    }
+
+    Ok((datpaths, limpaths, cfgpath))
+}
+
+/// check the limits files against the data, prints the issues and returns their count
+fn lint(datpaths: &[String], limpaths: &[String], cfgpath: &String) -> Result<usize, String> {
+    if limpaths.is_empty() {
+        return Err("HINT: lint needs at least one CSV limits file.".to_string());
+    }
+    let (qtableprops, _) = read_config(cfgpath)?;
+    let names = parameter_names(datpaths, &qtableprops)?;
+
+    let mut count = 0;
+    for limpath in limpaths.iter() {
+        let issues = lint_limits(limpath, &names, &qtableprops)?;
+        for issue in issues.iter() {
+            println!("{}: {}", limpath, issue);
+        }
+        println!(
+            "{} issues found in limits file '{}'.",
+            issues.len(),
+            limpath
+        );
+        count += issues.len();
+    }
+    Ok(count)
 }
 
 pub fn read_config(cfgpath: &String) -> Result<(QTableProps, Vec<Column>), String> {
-    let empty_val_allowed = vec!["pdffolder", "longgroupnames", "nanstring", "groupby"];
+    let empty_val_allowed = [
+        "pdffolder",
+        "longgroupnames",
+        "nanstring",
+        "groupby",
+        "longkeys",
+    ];
 
     let mut allowed_vals: HashMap<String, Vec<String>> = HashMap::new();
 
@@ -245,9 +447,12 @@ pub fn read_config(cfgpath: &String) -> Result<(QTableProps, Vec<Column>), Strin
     let mut qtableprops = default_props();
 
     let mut columns: Vec<Column> = vec![];
+    // select rows are parsed with the dialect of the data files, which may be set in later rows
+    let mut selects: Vec<String> = vec![];
 
     if *cfgpath != "".to_string() {
-        let mut rdr: Reader<File> = match Reader::from_path(cfgpath) {
+        // the config file itself is always read with auto detected dialect
+        let (mut rdr, cfgdialect) = match open_reader(cfgpath, &Dialect::default()) {
             Err(e) => {
                 return Err(format!(
                     "cannot not use CSV configfile '{}': '{}'.",
@@ -273,11 +478,7 @@ pub fn read_config(cfgpath: &String) -> Result<(QTableProps, Vec<Column>), Strin
         };
 
         for (i, h) in headers.iter().enumerate() {
-            let v = h.to_uppercase();
-            let w = &v;
-            let u: &str = &w;
-
-            match u {
+            match h.to_uppercase().as_str() {
                 "USE" => {
                     cfgcolumns.insert("use".to_string(), i);
                     columnscfg.insert(i, "use".to_string());
@@ -370,7 +571,7 @@ pub fn read_config(cfgpath: &String) -> Result<(QTableProps, Vec<Column>), Strin
             let mut val_ori: &str = Default::default();
             let mut nam: &str = Default::default();
             let mut wid: &str = Default::default();
-            let mut args: Vec<&str> = Default::default();
+            let mut args: Vec<String> = Default::default();
 
             for (i, v) in record.iter().enumerate() {
                 for cfg in cfgcolnames.iter() {
@@ -379,17 +580,7 @@ pub fn read_config(cfgpath: &String) -> Result<(QTableProps, Vec<Column>), Strin
                             &"opt" => {
                                 opt = v;
                                 if opt == "groupby" {
-                                    let mut c = HashMap::new();
-                                    for (i, p) in record.iter().enumerate() {
-                                        if columnscfg.contains_key(&i)
-                                            && (columnscfg.get(&i).unwrap() == "use"
-                                                || columnscfg.get(&i).unwrap() == "opt")
-                                            || p.is_empty()
-                                            || c.contains_key(p)
-                                        {
-                                            continue;
-                                        }
-                                        c.insert(p, true);
+                                    for p in opt_cells(&record, &columnscfg) {
                                         match p.parse::<usize>() {
                                             Ok(u) => {
                                                 qtableprops.group_by.push(GroupBy::ColNumber(u));
@@ -402,6 +593,11 @@ pub fn read_config(cfgpath: &String) -> Result<(QTableProps, Vec<Column>), Strin
                                         }
                                     }
                                 }
+                                if opt == "longkeys" {
+                                    for p in opt_cells(&record, &columnscfg) {
+                                        qtableprops.long_keys.push(p.into());
+                                    }
+                                }
                             }
                             &"val" => val_ori = v,
                             &"nam" => nam = v,
@@ -412,7 +608,7 @@ pub fn read_config(cfgpath: &String) -> Result<(QTableProps, Vec<Column>), Strin
                                         continue;
                                     }
                                     if !x.is_empty() {
-                                        args.push(x)
+                                        args.push(cfgdialect.normalize(x))
                                     }
                                 }
                             }
@@ -423,17 +619,22 @@ pub fn read_config(cfgpath: &String) -> Result<(QTableProps, Vec<Column>), Strin
             }
 
             let opt = opt.to_lowercase();
-            let val = val_ori.to_lowercase();
+            let val = cfgdialect.normalize(val_ori).to_lowercase();
 
             let opt = opt.trim();
             let val = val.trim();
-            let nam = nam.trim().to_string();
-            let wid = wid.trim();
+            let nam = cfgdialect.normalize(nam.trim());
+            let wid = cfgdialect.normalize(wid.trim());
 
             match opt {
                 "caption" => {
                     qtableprops.caption = val.to_string();
                 }
+                "processing" => match val {
+                    "memory" => qtableprops.streaming = false,
+                    "streaming" => qtableprops.streaming = true,
+                    _ => return Err(unknown_val(opt, val)),
+                },
                 "captionwhere" => match val {
                     "firstpage" => qtableprops.captioneverypage = false,
                     "everypage" => qtableprops.captioneverypage = true,
@@ -532,6 +733,11 @@ pub fn read_config(cfgpath: &String) -> Result<(QTableProps, Vec<Column>), Strin
                         _ => return Err(unknown_val(opt, val)),
                     }
                 }
+                "limitsreport" => match val {
+                    "no" => qtableprops.limits_report = false,
+                    "yes" => qtableprops.limits_report = true,
+                    _ => return Err(unknown_val(opt, val)),
+                },
                 "groupnames" => match val {
                     "numbers" => qtableprops.longgroupnames = false,
                     "longnames" => qtableprops.longgroupnames = true,
@@ -567,6 +773,7 @@ pub fn read_config(cfgpath: &String) -> Result<(QTableProps, Vec<Column>), Strin
                             "specyield" => mark |= Mark::SpecYield,
                             "ctrlyield" => mark |= Mark::ControlYield,
                             "cpk" => mark |= Mark::Cpk,
+                            "completeness" => mark |= Mark::Completeness,
                             _ => return Err(unknown_val(opt, val)),
                         }
                     }
@@ -577,6 +784,12 @@ pub fn read_config(cfgpath: &String) -> Result<(QTableProps, Vec<Column>), Strin
                 "nanstring" => {
                     qtableprops.nanstring = val.to_string();
                 }
+                "quantile" => {
+                    qtableprops.quantile = match val.parse::<usize>().ok().and_then(Quantile::from_number) {
+                        Some(q) => q,
+                        None => return Err(unknown_val(opt, val)),
+                    }
+                }
                 "sigdigits" => {
                     if !val.is_empty() {
                         qtableprops.sig_digits = match val.parse::<usize>() {
@@ -597,7 +810,7 @@ pub fn read_config(cfgpath: &String) -> Result<(QTableProps, Vec<Column>), Strin
                         qtableprops.float_limit = match val.parse::<f64>() {
                             Ok(v) => v.abs(),
                             Err(_) => {
-                                let v = std::f64::MAX;
+                                let v = f64::MAX;
                                 println!(
                                     "VAL '{}' for OPT '{}' is invalid, using '{}' instead.",
                                     val, opt, v
@@ -645,6 +858,73 @@ pub fn read_config(cfgpath: &String) -> Result<(QTableProps, Vec<Column>), Strin
                         }
                     }
                 }
+                "completenesslimit" => {
+                    qtableprops.completeness_limit = match val.parse::<f64>() {
+                        Ok(v) if v >= 0.0 => v.min(100.0),
+                        _ => {
+                            let v = 90.0;
+                            println!(
+                                "VAL '{}' for OPT '{}' is invalid, using '{}' instead.",
+                                val, opt, v
+                            );
+                            v
+                        }
+                    }
+                }
+                "derive" => {
+                    let number = |s: &str, default: f64| match s.parse::<f64>() {
+                        Ok(v) if v.is_finite() && v >= 0.0 => v,
+                        _ => {
+                            if !s.is_empty() {
+                                println!("In derive {}: '{}' is not a number, using {} instead", val, s, default);
+                            }
+                            default
+                        }
+                    };
+                    qtableprops.derive = match val {
+                        "sigma" => Derive::Sigma(number(&nam, 6.0), number(&wid, 3.0)),
+                        "percentile" => Derive::Percentile(number(&nam, 0.135), number(&wid, 2.275)),
+                        "tolerance" => Derive::Tolerance(
+                            number(&nam, 99.73),
+                            number(&wid, 95.45),
+                            number(args.first().map(|a| a.as_str()).unwrap_or(""), 95.0),
+                        ),
+                        _ => return Err(unknown_val(opt, val)),
+                    };
+                    let within = |x: &f64, high: f64| *x > 0.0 && *x < high;
+                    match &qtableprops.derive {
+                        Derive::Percentile(s, c) if !within(s, 50.0) || !within(c, 50.0) => {
+                            return Err(format!(
+                                "percentiles NAM '{}' and WID '{}' of OPT 'derive' in configfile '{}' must be above 0 and below 50.",
+                                s, c, cfgpath
+                            ))
+                        }
+                        Derive::Tolerance(s, c, confidence)
+                            if !within(s, 100.0) || !within(c, 100.0) || !within(confidence, 100.0) =>
+                        {
+                            return Err(format!(
+                                "coverages NAM '{}', WID '{}' and confidence ARG '{}' of OPT 'derive' in configfile '{}' must be above 0 and below 100 percent.",
+                                s, c, confidence, cfgpath
+                            ))
+                        }
+                        _ => (),
+                    }
+                }
+                "derivekeepspec" => match val {
+                    "yes" => qtableprops.derive_keep_spec = true,
+                    "no" => qtableprops.derive_keep_spec = false,
+                    _ => return Err(unknown_val(opt, val)),
+                },
+                "derivegroups" => match val {
+                    "yes" => qtableprops.derive_groups = true,
+                    "no" => qtableprops.derive_groups = false,
+                    _ => return Err(unknown_val(opt, val)),
+                },
+                "propose" => match val {
+                    "yes" => qtableprops.propose = true,
+                    "no" => qtableprops.propose = false,
+                    _ => return Err(unknown_val(opt, val)),
+                },
                 "cpklimit" => {
                     qtableprops.cpk_limit = match val.parse::<f64>() {
                         Ok(v) => {
@@ -667,6 +947,150 @@ pub fn read_config(cfgpath: &String) -> Result<(QTableProps, Vec<Column>), Strin
                 "groupby" => {
                     // see "opt" above
                 }
+                "datformat" => match val {
+                    "wide" => qtableprops.datformat = DatFormat::Wide,
+                    "long" => qtableprops.datformat = DatFormat::Long,
+                    _ => return Err(unknown_val(opt, val)),
+                },
+                "longparameter" => {
+                    qtableprops.long_parameter = val_ori.trim().to_string();
+                }
+                "timestamp" => {
+                    qtableprops.time_column = val_ori.trim().to_string();
+                    qtableprops.time_format = nam.to_string();
+                }
+                "timefrom" | "timeto" => {
+                    let parsed = match opt {
+                        "timefrom" => parse_time(val_ori, ""),
+                        _ => parse_time_to(val_ori, ""),
+                    };
+                    let t = match parsed {
+                        Some(t) => t,
+                        None => {
+                            return Err(format!(
+                                "VAL '{}' for OPT '{}' in configfile '{}' is not a date/time like '2024-01-31' or '2024-01-31 12:00:00'.",
+                                val_ori, opt, cfgpath
+                            ))
+                        }
+                    };
+                    match opt {
+                        "timefrom" => qtableprops.time_from = Some(t),
+                        _ => qtableprops.time_to = Some(t),
+                    }
+                }
+                "limitsdate" => {
+                    qtableprops.limits_date = match val {
+                        "now" => LimitsDate::Now,
+                        "timestamp" => LimitsDate::Timestamp,
+                        _ => match parse_time(val_ori, "") {
+                            Some(t) => LimitsDate::At(t),
+                            None => {
+                                return Err(format!(
+                                    "VAL '{}' for OPT '{}' in configfile '{}' is not now, timestamp or a date/time like '2024-01-31'.",
+                                    val_ori, opt, cfgpath
+                                ))
+                            }
+                        },
+                    }
+                }
+                "timelastdays" => {
+                    qtableprops.time_last_days = match val.parse::<f64>() {
+                        Ok(v) if v > 0.0 => Some(v),
+                        _ => {
+                            return Err(format!(
+                                "VAL '{}' for OPT '{}' in configfile '{}' must be a number of days > 0.",
+                                val, opt, cfgpath
+                            ))
+                        }
+                    };
+                }
+                "sentinel" => {
+                    let bounds: Vec<Option<f64>> = val.split("..").map(|v| v.trim().parse::<f64>().ok()).collect();
+                    let (low, high) = match bounds[..] {
+                        [Some(v)] => (v, v),
+                        [Some(low), Some(high)] if low <= high => (low, high),
+                        _ => {
+                            return Err(format!(
+                                "VAL '{}' for OPT '{}' in configfile '{}' must be a number or a range like '1e30..1e38'.",
+                                val, opt, cfgpath
+                            ))
+                        }
+                    };
+                    let label = match nam.is_empty() {
+                        true => val.to_string(),
+                        false => nam.to_string(),
+                    };
+                    qtableprops.sentinels.push(Sentinel { low, high, label });
+                }
+                "unitsrow" => match val {
+                    "auto" => qtableprops.units_row = UnitsRow::Auto,
+                    "yes" => qtableprops.units_row = UnitsRow::Yes,
+                    "no" => qtableprops.units_row = UnitsRow::No,
+                    _ => return Err(unknown_val(opt, val)),
+                },
+                "columnkind" => {
+                    let kind = match val {
+                        "numeric" => ColumnKind::Numeric,
+                        "categorical" => ColumnKind::Categorical,
+                        "timestamp" => ColumnKind::Timestamp,
+                        "id" => ColumnKind::Id,
+                        _ => return Err(unknown_val(opt, val)),
+                    };
+                    let names = std::iter::once(nam.clone()).chain(args.iter().cloned());
+                    for name in names.filter(|n| !n.is_empty()) {
+                        qtableprops.column_kinds.push((name, kind.clone()));
+                    }
+                }
+                "select" => {
+                    selects.push(val_ori.trim().to_string());
+                }
+                "include" => {
+                    qtableprops.include.push(ParamPattern::parse(val_ori)?);
+                }
+                "exclude" => {
+                    qtableprops.exclude.push(ParamPattern::parse(val_ori)?);
+                }
+                "longvalue" => {
+                    qtableprops.long_value = val_ori.trim().to_string();
+                }
+                "longkeys" => {
+                    // see "opt" above
+                }
+                "delimiter" => match val {
+                    "auto" => qtableprops.dialect.delimiter = None,
+                    "comma" => qtableprops.dialect.delimiter = Some(b','),
+                    "semicolon" => qtableprops.dialect.delimiter = Some(b';'),
+                    "tab" => qtableprops.dialect.delimiter = Some(b'\t'),
+                    "pipe" => qtableprops.dialect.delimiter = Some(b'|'),
+                    "space" => qtableprops.dialect.delimiter = Some(b' '),
+                    _ => return Err(unknown_val(opt, val)),
+                },
+                "quote" => match val {
+                    "doublequote" => qtableprops.dialect.quote = b'"',
+                    "singlequote" => qtableprops.dialect.quote = b'\'',
+                    _ => return Err(unknown_val(opt, val)),
+                },
+                "decimal" => match val {
+                    "auto" => qtableprops.dialect.decimal = None,
+                    "point" => qtableprops.dialect.decimal = Some('.'),
+                    "comma" => qtableprops.dialect.decimal = Some(','),
+                    _ => return Err(unknown_val(opt, val)),
+                },
+                "thousands" => match val {
+                    "none" => qtableprops.dialect.thousands = None,
+                    "point" => qtableprops.dialect.thousands = Some('.'),
+                    "comma" => qtableprops.dialect.thousands = Some(','),
+                    "space" => qtableprops.dialect.thousands = Some(' '),
+                    "apostrophe" => qtableprops.dialect.thousands = Some('\''),
+                    _ => return Err(unknown_val(opt, val)),
+                },
+                "encoding" => match val {
+                    "auto" => qtableprops.dialect.encoding = None,
+                    _ => match encoding_for_label(val) {
+                        Some(e) => qtableprops.dialect.encoding = Some(e),
+                        None => return Err(unknown_val(opt, val)),
+                    },
+                },
                 "longgroupnames" => {
                     qtableprops.longgroupnames = true;
                 }
@@ -686,6 +1110,9 @@ pub fn read_config(cfgpath: &String) -> Result<(QTableProps, Vec<Column>), Strin
                         match val {
                             "number" => columns.push(Column::Number(nam, w)),
                             "parameter" => columns.push(Column::Parameter(nam, w)),
+                            "unit" => columns.push(Column::Unit(nam, w)),
+                            "revision" => columns.push(Column::Revision(nam, w)),
+                            "limitsource" => columns.push(Column::LimitsSource(nam, w)),
                             "count" => columns.push(Column::Count(nam, w)),
                             "mean" => columns.push(Column::Mean(nam, w)),
                             "median" => columns.push(Column::Median(nam, w)),
@@ -699,6 +1126,23 @@ pub fn read_config(cfgpath: &String) -> Result<(QTableProps, Vec<Column>), Strin
                             "k" => columns.push(Column::K(nam, w)),
                             "cpk" => columns.push(Column::Cpk(nam, w)),
                             "cp" => columns.push(Column::Cp(nam, w)),
+                            "codes" => columns.push(Column::Codes(nam, w)),
+                            "codecount" => match args.first() {
+                                Some(label) => columns.push(Column::CodeCount(nam, w, label.clone())),
+                                None => {
+                                    return Err(format!(
+                                        "column '{}' in configfile {} needs the error code label in ARG.",
+                                        val, cfgpath
+                                    ))
+                                }
+                            },
+                            "codeyield" => columns.push(Column::CodeYield(nam, w)),
+                            "missing" => columns.push(Column::Missing(nam, w)),
+                            "unparseable" => columns.push(Column::Unparseable(nam, w)),
+                            "nancount" => columns.push(Column::NanCount(nam, w)),
+                            "cutoff" => columns.push(Column::Cutoff(nam, w)),
+                            "filtered" => columns.push(Column::Filtered(nam, w)),
+                            "completeness" => columns.push(Column::Completeness(nam, w)),
                             "percentile" => {
                                 if !args.is_empty() {
                                     let f = match args[0].parse::<f64>() {
@@ -741,5 +1185,43 @@ pub fn read_config(cfgpath: &String) -> Result<(QTableProps, Vec<Column>), Strin
         columns = default_columns();
     }
 
+    for select in selects.iter() {
+        qtableprops
+            .select
+            .push(Predicate::parse(select, &qtableprops.dialect)?);
+    }
+
+    let time_window = qtableprops.time_from.is_some()
+        || qtableprops.time_to.is_some()
+        || qtableprops.time_last_days.is_some();
+    if time_window && qtableprops.time_column.is_empty() {
+        return Err(format!(
+            "OPT 'timefrom', 'timeto' or 'timelastdays' in configfile '{}' needs the OPT 'timestamp'.",
+            cfgpath
+        ));
+    }
+    if qtableprops.limits_date == LimitsDate::Timestamp && qtableprops.time_column.is_empty() {
+        return Err(format!(
+            "OPT 'limitsdate' with VAL 'timestamp' in configfile '{}' needs the OPT 'timestamp'.",
+            cfgpath
+        ));
+    }
+
     Ok((qtableprops, columns))
 }
+
+/// distinct non-empty cells of a config row right of the USE and OPT columns
+fn opt_cells<'a>(record: &'a StringRecord, columnscfg: &HashMap<usize, String>) -> Vec<&'a str> {
+    let mut cells: Vec<&str> = vec![];
+    for (i, p) in record.iter().enumerate() {
+        if columnscfg.contains_key(&i)
+            && (columnscfg.get(&i).unwrap() == "use" || columnscfg.get(&i).unwrap() == "opt")
+            || p.is_empty()
+            || cells.contains(&p)
+        {
+            continue;
+        }
+        cells.push(p);
+    }
+    cells
+}
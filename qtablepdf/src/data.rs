@@ -1,179 +1,1220 @@
 //! data table types and methods
-use crate::limits::LimitsTable;
+use crate::config::QTableProps;
+use crate::derive::derive_limits;
+use crate::expr::Stat;
+use crate::group::Groups;
 use crate::limits::LimitsTableExt;
+use crate::limits::{Limits, LimitsTable};
 use crate::numbers;
 use crate::qtable::Filter;
+use crate::select::{is_selected, report_selected, select_columns};
+use crate::time::{parse_time, TimeRange, TimeWindow};
 extern crate csv;
+use crate::dialect::{open_reader, Dialect};
 use crate::numbers::F64Ext;
-use csv::Reader;
+use crate::qdas::{is_qdas, read_qdas, Qdas, QDAS_GROUP_KEYS};
+use crate::stdf::{is_stdf, read_stdf, Stdf, STDF_KEYS};
+use crate::workbook::file_path;
 use csv::Writer;
-use std::collections::{BTreeMap, HashMap};
+use csv::{Reader, StringRecord};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fmt;
-use std::fs::File;
+use std::io::Read;
 use std::path::{Path, PathBuf};
 
+/// distinct strings of a column above which columns of mostly numbers keep no strings
+const INTERN_LIMIT: usize = 1024;
+
+/// one bit per row of a column, e.g. the validity of its values
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Bitmap {
+    bits: Vec<u64>,
+    len: usize,
+    ones: usize,
+}
+
+impl Bitmap {
+    pub fn push(&mut self, bit: bool) {
+        if self.bits.len() * 64 == self.len {
+            self.bits.push(0);
+        }
+        if bit {
+            self.bits[self.len / 64] |= 1 << (self.len % 64);
+            self.ones += 1;
+        }
+        self.len += 1;
+    }
+
+    pub fn get(&self, i: usize) -> bool {
+        i < self.len && self.bits[i / 64] & (1 << (i % 64)) != 0
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn count_ones(&self) -> usize {
+        self.ones
+    }
+}
+
+/// strings of a column stored once and referenced by a code per row
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Interned {
+    pub codes: Vec<u32>,
+    pub dict: Vec<String>,
+    index: HashMap<String, u32>,
+}
+
+impl Interned {
+    pub fn push(&mut self, v: &str) {
+        let code = match self.index.get(v) {
+            Some(code) => *code,
+            None => {
+                let code = self.dict.len() as u32;
+                self.dict.push(v.to_string());
+                self.index.insert(v.to_string(), code);
+                code
+            }
+        };
+        self.codes.push(code);
+    }
+
+    pub fn get(&self, i: usize) -> &str {
+        &self.dict[self.codes[i] as usize]
+    }
+}
+
+/// one column of the data file.
+/// Numbers are parsed once into `nums` (NaN where `valid` is not set),
+/// the cells are kept as interned strings unless the column is mostly numbers with too many distinct values,
+/// cells neither empty nor numbers are marked in `unparseable`.
 pub struct Data {
     pub name: String,
     pub filt: Filter,
-    pub vals: Vec<String>,
+    pub nums: Vec<f64>,
+    pub valid: Bitmap,
+    pub unparseable: Bitmap,
+    pub text: Option<Interned>,
+    pub kind: ColumnKind,
+    /// unit of the values, may be empty
+    pub unit: String,
+    /// included and not excluded as parameter by the config
+    pub selected: bool,
+    /// kind set by the reader of the data file, e.g. of key columns, kept by the classification
+    pub kind_given: bool,
+    /// a cell not parseable as number was pushed
+    has_text: bool,
+}
+
+/// minimum count of distinct values of a column with unique values to be an ID column
+const ID_MIN_COUNT: usize = 10;
+
+/// headers of numeric ID columns, compared in upper case without separators
+const ID_NAMES: [&str; 16] = [
+    "ID",
+    "SN",
+    "SERIAL",
+    "SERIALNO",
+    "SERIALNR",
+    "SERIALNUMBER",
+    "PARTID",
+    "UNITID",
+    "DEVICEID",
+    "DIEID",
+    "INDEX",
+    "IDX",
+    "ROW",
+    "ROWNO",
+    "NR",
+    "NO",
+];
+
+/// true, if the header is a known name of an ID column
+fn is_id_name(name: &str) -> bool {
+    let name: String = name
+        .chars()
+        .filter(|c| c.is_alphanumeric())
+        .collect::<String>()
+        .to_uppercase();
+    ID_NAMES.contains(&name.as_str())
+}
+
+/// what the values of a column are, only numeric columns are rendered as parameters
+#[derive(Debug, Clone, PartialEq)]
+pub enum ColumnKind {
+    Numeric,
+    /// e.g. BATCH/LOT or PRODUCT/TYPE, can be used for 'groupby'
+    Categorical,
+    Timestamp,
+    /// unique value per row, e.g. serial numbers
+    Id,
 }
 
 pub type DataTable = BTreeMap<usize, Data>;
 
+impl Data {
+    pub fn new(name: &str, filt: Filter) -> Data {
+        Data {
+            name: name.to_string(),
+            filt,
+            nums: vec![],
+            valid: Bitmap::default(),
+            unparseable: Bitmap::default(),
+            text: Some(Interned::default()),
+            kind: ColumnKind::Numeric,
+            unit: "".to_string(),
+            selected: true,
+            kind_given: false,
+            has_text: false,
+        }
+    }
+
+    /// column of keys identifying the rows, e.g. LOT or PART, categorical even with numbers only
+    pub fn key(name: &str, filt: Filter) -> Data {
+        let mut data = Data::new(name, filt);
+        data.kind = ColumnKind::Categorical;
+        data.kind_given = true;
+        data
+    }
+
+    /// add a cell, parsed as number with decimal point
+    pub fn push(&mut self, v: &str) {
+        let num = v.trim().parse::<f64>().ok();
+        self.push_parsed(v, num);
+    }
+
+    /// add a cell already parsed as number, if possible
+    pub fn push_parsed(&mut self, v: &str, num: Option<f64>) {
+        let unparseable = num.is_none() && !v.trim().is_empty();
+        self.nums.push(num.unwrap_or(f64::NAN));
+        self.valid.push(num.is_some());
+        self.unparseable.push(unparseable);
+        self.has_text |= unparseable;
+        if let Some(text) = self.text.as_mut() {
+            text.push(v);
+            // strings of a column of numbers with a few like N/A are not needed, they are counted only
+            if text.dict.len() > INTERN_LIMIT
+                && self.unparseable.count_ones() * 2 < self.valid.count_ones()
+            {
+                self.text = None;
+            }
+        }
+    }
+
+    /// add row i of another column
+    fn push_row(&mut self, other: &Data, i: usize) {
+        match &other.text {
+            Some(text) => self.push_parsed(text.get(i), other.num(i)),
+            None => {
+                self.nums.push(other.nums[i]);
+                self.valid.push(other.valid.get(i));
+                self.unparseable.push(other.unparseable.get(i));
+                self.has_text |= other.unparseable.get(i);
+                // the other column is mostly numbers, the strings are not kept either
+                self.text = None;
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.nums.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nums.is_empty()
+    }
+
+    /// cell of row i as string
+    pub fn value(&self, i: usize) -> String {
+        match &self.text {
+            Some(text) => text.get(i).to_string(),
+            None => match self.valid.get(i) {
+                true => self.nums[i].to_string(),
+                false => "".to_string(),
+            },
+        }
+    }
+
+    /// numbers of the rows given by indices, e.g. of a group
+    pub fn select(&self, indices: &[usize]) -> Vec<f64> {
+        indices.iter().map(|i| self.nums[*i]).collect()
+    }
+
+    /// number of row i, if the cell is a number
+    pub fn num(&self, i: usize) -> Option<f64> {
+        match self.valid.get(i) {
+            true => Some(self.nums[i]),
+            false => None,
+        }
+    }
+
+    /// counts of the empty and the unparseable cells of all rows or of the rows given by indices
+    pub fn invalid_counts(&self, indices: Option<&[usize]>) -> (f64, f64) {
+        let (mut missing, mut unparseable) = (0.0, 0.0);
+        let mut count = |i: usize| {
+            if self.unparseable.get(i) {
+                unparseable += 1.0;
+            } else if !self.valid.get(i) {
+                missing += 1.0;
+            }
+        };
+        match indices {
+            Some(indices) => indices.iter().for_each(|i| count(*i)),
+            None => (0..self.len()).for_each(count),
+        }
+        (missing, unparseable)
+    }
+
+    /// add all rows of another column
+    pub fn append(&mut self, other: &Data) {
+        if self.unit.is_empty() {
+            self.unit = other.unit.clone();
+        }
+        if other.kind_given {
+            self.kind = other.kind.clone();
+            self.kind_given = true;
+        }
+        for i in 0..other.len() {
+            self.push_row(other, i);
+        }
+    }
+
+    /// keep only the rows set in keep
+    pub fn retain(&mut self, keep: &[bool]) {
+        let mut data = Data::new(&self.name, self.filt.clone());
+        data.unit = self.unit.clone();
+        data.kind = self.kind.clone();
+        data.kind_given = self.kind_given;
+        for i in (0..self.len()).filter(|i| keep[*i]) {
+            data.push_row(self, i);
+        }
+        *self = data;
+    }
+
+    /// kind of the column derived from its values, unless given by the reader.
+    /// Numbers like serial numbers are an ID only with an ID header or without limits of a limits file.
+    pub fn classify(&self, qtableprops: &QTableProps, limitstable: &LimitsTable) -> ColumnKind {
+        if self.name == qtableprops.time_column {
+            return ColumnKind::Timestamp;
+        }
+        if self.kind_given {
+            return self.kind.clone();
+        }
+        if !self.has_text {
+            let not_in_limits =
+                !limitstable.is_empty() && limitstable.get_row(&self.name).is_none();
+            return match self.is_serial() && (is_id_name(&self.name) || not_in_limits) {
+                true => ColumnKind::Id,
+                false => ColumnKind::Numeric,
+            };
+        }
+        // strings are dropped only for columns of mostly numbers
+        let text = match &self.text {
+            Some(text) => text,
+            None => return ColumnKind::Numeric,
+        };
+        let distinct: Vec<&String> = text.dict.iter().filter(|v| !v.trim().is_empty()).collect();
+        if distinct.iter().all(|v| parse_time(v, "").is_some()) {
+            return ColumnKind::Timestamp;
+        }
+        let filled = text
+            .codes
+            .iter()
+            .filter(|c| !text.dict[**c as usize].trim().is_empty())
+            .count();
+        match distinct.len() >= ID_MIN_COUNT && distinct.len() == filled {
+            true => ColumnKind::Id,
+            false => ColumnKind::Categorical,
+        }
+    }
+
+    /// true, if the numbers are distinct integers counting up or down or filling a range, like serial numbers
+    fn is_serial(&self) -> bool {
+        let nums: Vec<f64> = (0..self.len()).filter_map(|i| self.num(i)).collect();
+        if nums.len() < ID_MIN_COUNT || nums.iter().any(|x| x.fract() != 0.0) {
+            return false;
+        }
+        if nums.windows(2).all(|w| w[0] < w[1]) || nums.windows(2).all(|w| w[0] > w[1]) {
+            return true;
+        }
+        let mut sorted = nums;
+        sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        sorted.windows(2).all(|w| w[1] - w[0] == 1.0)
+    }
+
+    fn head(&self, n: usize) -> Vec<String> {
+        (0..self.len().min(n)).map(|i| self.value(i)).collect()
+    }
+}
+
 impl fmt::Debug for Data {
     fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
         write!(
             formatter,
             "Data({:?}, len:{:?}), values:{:?}",
             self.name,
-            self.vals.len(),
-            &self.vals
+            self.len(),
+            &self.head(self.len())
         )
     }
 }
 
 impl fmt::Display for Data {
     fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
-        let l = match self.vals.len() < 3 {
-            true => self.vals.len(),
-            false => 3,
-        };
         write!(
             formatter,
             "Data({:?}, len:{:?}), values:{:#?}",
             self.name,
-            self.vals.len(),
-            &self.vals[0..l]
+            self.len(),
+            &self.head(3)
         )
     }
 }
 
+/// layout of the data file
+#[derive(Debug, Clone, PartialEq)]
+pub enum DatFormat {
+    /// every parameter is a column
+    Wide,
+    /// one row per unit/parameter/value, pivoted by key columns
+    Long,
+}
+
+/// column with the name of the data file of each row, if more than one data file is read
+pub const SOURCE: &str = "SOURCE";
+
+/// match the columns of several data files by name and occurrence of the name.
+/// Returns the merged names in order of first appearance
+/// and per file the column index of each merged name, if the file has it.
+pub(crate) fn merge_columns(files: &[Vec<String>]) -> (Vec<String>, Vec<Vec<Option<usize>>>) {
+    let mut names: Vec<String> = vec![];
+    let mut merged: HashMap<(String, usize), usize> = HashMap::new();
+    let mut mapping = vec![];
+    for headers in files.iter() {
+        let mut occurrence: HashMap<&String, usize> = HashMap::new();
+        let mut columns: Vec<Option<usize>> = vec![None; names.len()];
+        for (i, h) in headers.iter().enumerate() {
+            let n = occurrence.entry(h).or_insert(0);
+            let key = (h.clone(), *n);
+            *n += 1;
+            let k = match merged.get(&key) {
+                Some(k) => *k,
+                None => {
+                    merged.insert(key, names.len());
+                    names.push(h.clone());
+                    columns.push(None);
+                    names.len() - 1
+                }
+            };
+            columns[k] = Some(i);
+        }
+        mapping.push(columns);
+    }
+    for columns in mapping.iter_mut() {
+        columns.resize(names.len(), None);
+    }
+    (names, mapping)
+}
+
+/// tell the user which columns are missing in which data files
+pub(crate) fn report_missing(
+    names: &[String],
+    mapping: &[Vec<Option<usize>>],
+    datpaths: &[String],
+) {
+    for (k, name) in names.iter().enumerate() {
+        let missing: Vec<&str> = mapping
+            .iter()
+            .zip(datpaths.iter())
+            .filter(|(columns, _)| columns[k].is_none())
+            .map(|(_, datpath)| datpath.as_str())
+            .collect();
+        if !missing.is_empty() {
+            println!(
+                "column '{}' is missing in data file(s) '{}', its values are empty there.",
+                name,
+                missing.join("', '")
+            );
+        }
+    }
+}
+
+/// a STDF or Q-DAS data file, carrying values and limits
+pub enum ParsedFile {
+    Stdf(Stdf),
+    Qdas(Qdas),
+}
+
+/// STDF and Q-DAS data files by path, parsed once for their limits and their values
+pub type ParsedFiles = HashMap<String, ParsedFile>;
+
+/// parse the STDF and Q-DAS files of the data files, other files are read later
+pub fn parse_datafiles(
+    datpaths: &[String],
+    qtableprops: &QTableProps,
+) -> Result<ParsedFiles, String> {
+    let mut parsed = ParsedFiles::new();
+    for datpath in datpaths.iter() {
+        if is_stdf(datpath) {
+            parsed.insert(datpath.clone(), ParsedFile::Stdf(read_stdf(datpath)?));
+        } else if is_qdas(datpath) {
+            parsed.insert(
+                datpath.clone(),
+                ParsedFile::Qdas(read_qdas(datpath, &qtableprops.dialect)?),
+            );
+        }
+    }
+    Ok(parsed)
+}
+
+/// name of a data file used in the SOURCE column
+pub fn source_name(datpath: &str) -> String {
+    match Path::new(datpath).file_name() {
+        Some(name) => name.to_string_lossy().to_string(),
+        None => datpath.to_string(),
+    }
+}
+
+/// names of the parameters of the data files, read from the headers (or the parameter column
+/// of the long format) without reading the values. The key columns of STDF and Q-DAS files and the
+/// timestamp column are left out, the other columns of wide format files are all included.
+pub fn parameter_names(
+    datpaths: &[String],
+    qtableprops: &QTableProps,
+) -> Result<Vec<String>, String> {
+    let mut parsed = parse_datafiles(datpaths, qtableprops)?;
+    let mut names: Vec<String> = vec![];
+    for datpath in datpaths.iter() {
+        match parsed.remove(datpath) {
+            Some(ParsedFile::Stdf(stdf)) => {
+                names.extend(stdf.tests.into_iter().map(|t| t.name));
+                continue;
+            }
+            Some(ParsedFile::Qdas(qdas)) => {
+                names.extend(qdas.chars.into_iter().map(|c| c.name));
+                continue;
+            }
+            None => (),
+        }
+        let (mut rdr, _) = open_data(datpath, &qtableprops.dialect)?;
+        let headers = match rdr.headers() {
+            Ok(record) => record.clone(),
+            Err(e) => {
+                return Err(format!("could not read CSV datafile '{}': {}.", datpath, e));
+            }
+        };
+        match qtableprops.datformat {
+            DatFormat::Wide => names.extend(
+                headers
+                    .iter()
+                    .filter(|h| !h.is_empty() && *h != qtableprops.time_column)
+                    .map(|h| h.to_string()),
+            ),
+            DatFormat::Long => {
+                let parcol = match headers.iter().position(|h| h == qtableprops.long_parameter) {
+                    Some(i) => i,
+                    None => {
+                        return Err(format!(
+                            "column '{}' of long format not found in CSV datafile '{}'.",
+                            qtableprops.long_parameter, datpath
+                        ))
+                    }
+                };
+                for record in rdr.records().filter_map(|result| result.ok()) {
+                    let par = record.get(parcol).unwrap_or("");
+                    if !par.is_empty() {
+                        names.push(par.to_string());
+                    }
+                }
+            }
+        }
+    }
+    let mut seen: HashSet<String> = HashSet::new();
+    names.retain(|name| seen.insert(name.clone()));
+    Ok(names)
+}
+
 pub trait DataTableExt<T> {
     fn add_data(
         &mut self,
         datpath: &String,
+        parsed: &mut ParsedFiles,
+        limitstable: &LimitsTable,
+        qtableprops: &QTableProps,
+    ) -> Result<(), String>;
+    fn add_datafiles(
+        &mut self,
+        datpaths: &[String],
+        parsed: ParsedFiles,
         limitstable: &LimitsTable,
-        filter: &Filter,
+        qtableprops: &QTableProps,
     ) -> Result<(), String>;
 }
 
 impl DataTableExt<DataTable> for DataTable {
+    /// add a data file, STDF and Q-DAS files not in parsed are parsed now
     fn add_data(
         &mut self,
         datpath: &String,
+        parsed: &mut ParsedFiles,
         limitstable: &LimitsTable,
-        filter: &Filter,
+        qtableprops: &QTableProps,
     ) -> Result<(), String> {
-        let datfile = match File::open(datpath) {
-            Err(e) => {
-                return Err(format!("could not open CSV datafile '{}': {}.", datpath, e));
-            }
-            Ok(datfile) => datfile,
+        let file = match parsed.remove(datpath) {
+            Some(file) => Some(file),
+            None => parse_datafiles(std::slice::from_ref(datpath), qtableprops)?.remove(datpath),
         };
-        let mut rdr: Reader<File> = Reader::from_reader(datfile);
+        match file {
+            Some(ParsedFile::Stdf(stdf)) => {
+                return add_stdf_data(self, stdf, limitstable, qtableprops)
+            }
+            Some(ParsedFile::Qdas(qdas)) => {
+                return add_qdas_data(self, qdas, limitstable, qtableprops)
+            }
+            None => (),
+        }
+        match qtableprops.datformat {
+            DatFormat::Wide => add_wide_data(self, datpath, limitstable, qtableprops),
+            DatFormat::Long => add_long_data(self, datpath, limitstable, qtableprops),
+        }
+    }
 
-        let headers = match rdr.headers() {
-            Ok(record) => record,
-            Err(e) => {
-                return Err(format!("could not read CSV datafile '{}': {}.", datpath, e));
+    /// add one or several data files, selecting the rows and classifying the columns
+    fn add_datafiles(
+        &mut self,
+        datpaths: &[String],
+        mut parsed: ParsedFiles,
+        limitstable: &LimitsTable,
+        qtableprops: &QTableProps,
+    ) -> Result<(), String> {
+        match datpaths.len() {
+            1 => self.add_data(&datpaths[0], &mut parsed, limitstable, qtableprops)?,
+            _ => merge_datafiles(self, datpaths, &mut parsed, limitstable, qtableprops)?,
+        }
+        select_rows(self, qtableprops)?;
+        classify_columns(self, limitstable, qtableprops);
+        select_parameters(self, qtableprops);
+        Ok(())
+    }
+}
+
+/// add several data files concatenated by matching column names
+/// with the SOURCE column naming the file of each row
+fn merge_datafiles(
+    datatable: &mut DataTable,
+    datpaths: &[String],
+    parsed: &mut ParsedFiles,
+    limitstable: &LimitsTable,
+    qtableprops: &QTableProps,
+) -> Result<(), String> {
+    let mut tables = vec![];
+    for datpath in datpaths.iter() {
+        let mut table = DataTable::new();
+        table.add_data(datpath, parsed, limitstable, qtableprops)?;
+        tables.push(table.into_values().collect::<Vec<Data>>());
+    }
+
+    let headers: Vec<Vec<String>> = tables
+        .iter()
+        .map(|t| t.iter().map(|v| v.name.clone()).collect())
+        .collect();
+    let (names, mapping) = merge_columns(&headers);
+    report_missing(&names, &mapping, datpaths);
+
+    for (k, name) in names.iter().enumerate() {
+        let mut data = Data::new(name, limitstable.get_filter(name, &qtableprops.filter));
+        for (table, columns) in tables.iter().zip(mapping.iter()) {
+            let rows = table.first().map(|v| v.len()).unwrap_or(0);
+            match columns[k] {
+                Some(i) => data.append(&table[i]),
+                None => (0..rows).for_each(|_| data.push("")),
+            }
+        }
+        datatable.insert(k, data);
+    }
+
+    let mut source = Data::key(SOURCE, Filter::None);
+    for (table, datpath) in tables.iter().zip(datpaths.iter()) {
+        let rows = table.first().map(|v| v.len()).unwrap_or(0);
+        let name = source_name(datpath);
+        (0..rows).for_each(|_| source.push_parsed(&name, None));
+    }
+    datatable.insert(names.len(), source);
+    Ok(())
+}
+
+/// set the kind of every column from its values, overridden by the config OPT 'columnkind'
+fn classify_columns(
+    datatable: &mut DataTable,
+    limitstable: &LimitsTable,
+    qtableprops: &QTableProps,
+) {
+    for data in datatable.values_mut() {
+        data.kind = match qtableprops
+            .column_kinds
+            .iter()
+            .rev()
+            .find(|(n, _)| *n == data.name)
+        {
+            Some((_, kind)) => kind.clone(),
+            None => {
+                let kind = data.classify(qtableprops, limitstable);
+                if kind == ColumnKind::Id && !data.has_text && !data.kind_given {
+                    println!(
+                        "numeric column '{}' is taken as ID column and not rendered, set the config OPT 'columnkind' to numeric to keep it.",
+                        data.name
+                    );
+                }
+                kind
             }
         };
+    }
+}
 
-        let mut empty_header: HashMap<usize, usize> = HashMap::new();
-        let mut k = 0;
-        for (i, h) in headers.iter().enumerate() {
-            if h.is_empty() {
-                empty_header.insert(i, i);
-                continue;
+/// deselect the numeric columns not included or excluded by the config,
+/// they are kept for grouping and row selection
+fn select_parameters(datatable: &mut DataTable, qtableprops: &QTableProps) {
+    for (k, v) in datatable.iter_mut() {
+        v.selected = is_selected(&v.name, k + 1, &qtableprops.include, &qtableprops.exclude);
+    }
+    let numeric = datatable.values().filter(|v| v.kind == ColumnKind::Numeric);
+    report_selected(
+        numeric.clone().filter(|v| v.selected).count(),
+        numeric.count(),
+        &qtableprops.include,
+        &qtableprops.exclude,
+    );
+}
+
+/// key of the timestamp column in the data table, if one is set
+pub(crate) fn time_column(
+    datatable: &DataTable,
+    qtableprops: &QTableProps,
+) -> Result<Option<usize>, String> {
+    if qtableprops.time_column.is_empty() {
+        return Ok(None);
+    }
+    match datatable
+        .iter()
+        .find(|(_, v)| v.name == qtableprops.time_column)
+    {
+        Some((k, _)) => Ok(Some(*k)),
+        None => Err(format!(
+            "timestamp column '{}' not found in datafile.",
+            qtableprops.time_column
+        )),
+    }
+}
+
+/// statistic of the numeric column named par, e.g. for limits like `1.1*mean(PARAM_07)`, None if there is none
+pub fn column_statistic(
+    datatable: &DataTable,
+    stat: Stat,
+    par: &str,
+    qtableprops: &QTableProps,
+) -> Option<f64> {
+    let data = datatable
+        .values()
+        .find(|d| d.name == par && d.kind == ColumnKind::Numeric)?;
+    let numbers = numbers::Numbers::new(
+        &data.nums,
+        qtableprops.float_limit,
+        &qtableprops.sentinels,
+        &data.filt,
+        qtableprops.quantile,
+    );
+    Some(stat.of(&numbers))
+}
+
+/// earliest and latest timestamp of the rows
+pub fn time_range(datatable: &DataTable, qtableprops: &QTableProps) -> Result<TimeRange, String> {
+    let mut range = TimeRange::default();
+    if let Some(k) = time_column(datatable, qtableprops)? {
+        let data = &datatable[&k];
+        for i in 0..data.len() {
+            if let Some(t) = parse_time(&data.value(i), &qtableprops.time_format) {
+                range.add(t);
             }
+        }
+    }
+    Ok(range)
+}
 
-            let filter_outliers = limitstable.get_filter(h, &filter);
-            &self.insert(
-                k,
-                Data {
-                    name: h.to_string(),
-                    filt: filter_outliers,
-                    vals: vec![],
+/// remove the rows not satisfying all predicates or outside of the time window
+fn select_rows(datatable: &mut DataTable, qtableprops: &QTableProps) -> Result<(), String> {
+    let select = &qtableprops.select;
+    let window = TimeWindow::new(qtableprops);
+    if select.is_empty() && window.is_unbounded() {
+        return Ok(());
+    }
+    let names: Vec<String> = datatable.values().map(|v| v.name.clone()).collect();
+    let keys: Vec<usize> = datatable.keys().cloned().collect();
+    let columns = select_columns(&names, select)?;
+    let time_data = match window.is_unbounded() {
+        true => None,
+        false => time_column(datatable, qtableprops)?.map(|k| &datatable[&k]),
+    };
+
+    let rows = datatable.values().next().map(|v| v.len()).unwrap_or(0);
+    let mut untimed = 0;
+    let keep: Vec<bool> = (0..rows)
+        .map(|i| {
+            let in_window = match time_data {
+                None => true,
+                Some(data) => match parse_time(&data.value(i), &qtableprops.time_format) {
+                    Some(t) => window.contains(&t),
+                    None => {
+                        untimed += 1;
+                        false
+                    }
                 },
-            );
-            k += 1;
+            };
+            in_window
+                && select.iter().zip(columns.iter()).all(|(p, k)| {
+                    let data = &datatable[&keys[*k]];
+                    p.matches(&data.value(i), data.num(i))
+                })
+        })
+        .collect();
+    if untimed > 0 {
+        println!(
+            "{} rows without valid timestamp in column '{}' are skipped.",
+            untimed, qtableprops.time_column
+        );
+    }
+
+    for data in datatable.values_mut() {
+        data.retain(&keep);
+    }
+    Ok(())
+}
+
+/// rows after the first one compared with it to detect a units row
+pub(crate) const UNITS_LOOKAHEAD: usize = 100;
+
+/// how the row after the header is used
+#[derive(Debug, Clone, PartialEq)]
+pub enum UnitsRow {
+    /// a units row, if no cell is a number, a cell is above numbers and no cell is repeated below
+    Auto,
+    Yes,
+    No,
+}
+
+/// true, if the first record after the header row holds the units,
+/// later are the next records, e.g. the first rows of data with categorical values like BL3 in the first row
+pub(crate) fn is_units_row(
+    record: &StringRecord,
+    later: &[StringRecord],
+    dialect: &Dialect,
+    units_row: &UnitsRow,
+) -> bool {
+    match units_row {
+        UnitsRow::Yes => true,
+        UnitsRow::No => false,
+        UnitsRow::Auto => {
+            let filled: Vec<(usize, &str)> = record
+                .iter()
+                .map(|v| v.trim())
+                .enumerate()
+                .filter(|(_, v)| !v.is_empty())
+                .collect();
+            let below = |i: usize| later.iter().filter_map(move |r| r.get(i)).map(|v| v.trim());
+            !filled.is_empty()
+                && filled.iter().all(|(_, v)| dialect.parse_f64(v).is_none())
+                && filled
+                    .iter()
+                    .any(|(i, _)| below(*i).any(|v| dialect.parse_f64(v).is_some()))
+                && !filled.iter().any(|(i, v)| below(*i).any(|w| w == *v))
         }
+    }
+}
 
-        for result in rdr.records() {
-            let record = match result {
-                Err(_) => {
-                    continue;
-                }
-                Ok(record) => record,
-            };
-            let mut k = 0;
-            let mut empty_line = true;
-            for v in record.iter() {
-                if !v.is_empty() {
-                    empty_line = false;
-                    break;
-                }
+fn open_data(datpath: &str, dialect: &Dialect) -> Result<(Reader<Box<dyn Read>>, Dialect), String> {
+    match open_reader(datpath, dialect) {
+        Err(e) => Err(format!("could not open CSV datafile '{}': {}.", datpath, e)),
+        Ok(rdr) => Ok(rdr),
+    }
+}
+
+/// read a data file with every parameter as a column
+fn add_wide_data(
+    datatable: &mut DataTable,
+    datpath: &str,
+    limitstable: &LimitsTable,
+    qtableprops: &QTableProps,
+) -> Result<(), String> {
+    let (mut rdr, dialect) = open_data(datpath, &qtableprops.dialect)?;
+
+    let headers = match rdr.headers() {
+        Ok(record) => record,
+        Err(e) => {
+            return Err(format!("could not read CSV datafile '{}': {}.", datpath, e));
+        }
+    };
+
+    let mut empty_header: HashMap<usize, usize> = HashMap::new();
+    let mut k = 0;
+    for (i, h) in headers.iter().enumerate() {
+        if h.is_empty() {
+            empty_header.insert(i, i);
+            continue;
+        }
+
+        let filter_outliers = limitstable.get_filter(h, &qtableprops.filter);
+        datatable.insert(k, Data::new(h, filter_outliers));
+        k += 1;
+    }
+
+    let mut records = rdr
+        .records()
+        .filter_map(|result| result.ok())
+        .filter(|record| !record.iter().all(|v| v.is_empty()));
+    let mut ahead: Vec<StringRecord> = records.by_ref().take(UNITS_LOOKAHEAD + 1).collect();
+    if !ahead.is_empty() && is_units_row(&ahead[0], &ahead[1..], &dialect, &qtableprops.units_row) {
+        println!("units row found in data file '{}'.", datpath);
+        let units = ahead[0]
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| !empty_header.contains_key(i));
+        for (k, (_, unit)) in units.enumerate() {
+            if let Some(data) = datatable.get_mut(&k) {
+                data.unit = unit.trim().to_string();
             }
-            if empty_line {
+        }
+        ahead.remove(0);
+    }
+    for record in ahead.into_iter().chain(records) {
+        let mut k = 0;
+        for (i, v) in record.iter().enumerate() {
+            if empty_header.contains_key(&i) {
                 continue;
             }
-            for (i, v) in record.iter().enumerate() {
-                if empty_header.contains_key(&i) {
-                    continue;
-                }
-                &self.get_mut(&k).unwrap().vals.push(v.parse().unwrap());
-                k += 1;
+            datatable
+                .get_mut(&k)
+                .unwrap()
+                .push_parsed(v, dialect.parse_f64(v));
+            k += 1;
+        }
+    }
+    Ok(())
+}
+
+/// read the parametric test results of a STDF file with one column per test
+fn add_stdf_data(
+    datatable: &mut DataTable,
+    stdf: Stdf,
+    limitstable: &LimitsTable,
+    qtableprops: &QTableProps,
+) -> Result<(), String> {
+    let names = STDF_KEYS
+        .iter()
+        .map(|k| (k.to_string(), "".to_string()))
+        .chain(stdf.tests.iter().map(|t| (t.name.clone(), t.unit.clone())));
+    for (k, (name, unit)) in names.enumerate() {
+        let filter_outliers = limitstable.get_filter(&name, &qtableprops.filter);
+        let mut data = match k < STDF_KEYS.len() {
+            true => Data::key(&name, filter_outliers),
+            false => Data::new(&name, filter_outliers),
+        };
+        data.unit = unit;
+        datatable.insert(k, data);
+    }
+
+    for row in stdf.rows {
+        for (k, v) in row.into_iter().enumerate() {
+            datatable.get_mut(&k).unwrap().push(&v);
+        }
+    }
+    Ok(())
+}
+
+/// read the measured values of a Q-DAS file with one column per characteristic
+fn add_qdas_data(
+    datatable: &mut DataTable,
+    qdas: Qdas,
+    limitstable: &LimitsTable,
+    qtableprops: &QTableProps,
+) -> Result<(), String> {
+    let names = qdas
+        .keys
+        .iter()
+        .map(|k| (k.clone(), "".to_string()))
+        .chain(qdas.chars.iter().map(|c| (c.name.clone(), c.unit.clone())));
+    for (k, (name, unit)) in names.enumerate() {
+        let filter_outliers = limitstable.get_filter(&name, &qtableprops.filter);
+        let mut data = match k < qdas.keys.len() && QDAS_GROUP_KEYS.contains(&name.as_str()) {
+            true => Data::key(&name, filter_outliers),
+            false => Data::new(&name, filter_outliers),
+        };
+        data.unit = unit;
+        datatable.insert(k, data);
+    }
+
+    for row in qdas.rows {
+        for (k, v) in row.into_iter().enumerate() {
+            datatable.get_mut(&k).unwrap().push(&v);
+        }
+    }
+    Ok(())
+}
+
+/// value of the long format as read and parsed
+type LongCell = (String, Option<f64>);
+
+/// read a data file with one row per unit/parameter/value
+/// and pivot it into one column per parameter.
+/// Units are identified by the key columns, which become the leading columns.
+fn add_long_data(
+    datatable: &mut DataTable,
+    datpath: &str,
+    limitstable: &LimitsTable,
+    qtableprops: &QTableProps,
+) -> Result<(), String> {
+    if qtableprops.long_keys.is_empty() {
+        return Err(format!(
+            "long format of CSV datafile '{}' needs at least one key column (OPT longkeys).",
+            datpath
+        ));
+    }
+
+    let (mut rdr, dialect) = open_data(datpath, &qtableprops.dialect)?;
+
+    let headers = match rdr.headers() {
+        Ok(record) => record.clone(),
+        Err(e) => {
+            return Err(format!("could not read CSV datafile '{}': {}.", datpath, e));
+        }
+    };
+
+    let column = |name: &str| -> Result<usize, String> {
+        match headers.iter().position(|h| h == name) {
+            Some(i) => Ok(i),
+            None => Err(format!(
+                "column '{}' of long format not found in CSV datafile '{}'.",
+                name, datpath
+            )),
+        }
+    };
+
+    let parcol = column(&qtableprops.long_parameter)?;
+    let valcol = column(&qtableprops.long_value)?;
+    let mut keycols = vec![];
+    for key in qtableprops.long_keys.iter() {
+        keycols.push(column(key)?);
+    }
+
+    // units and parameters in order of their first appearance
+    let mut units: HashMap<Vec<String>, usize> = HashMap::new();
+    let mut unitkeys: Vec<Vec<String>> = vec![];
+    let mut params: HashMap<String, usize> = HashMap::new();
+    let mut parvals: Vec<(String, Vec<Option<LongCell>>)> = vec![];
+    let mut duplicates = 0;
+
+    for result in rdr.records() {
+        let record = match result {
+            Err(_) => {
+                continue;
             }
+            Ok(record) => record,
+        };
+        let par = record.get(parcol).unwrap_or("");
+        if par.is_empty() {
+            continue;
         }
-        Ok(())
+        let key: Vec<String> = keycols
+            .iter()
+            .map(|i| record.get(*i).unwrap_or("").to_string())
+            .collect();
+        let u = match units.get(&key) {
+            Some(u) => *u,
+            None => {
+                let u = unitkeys.len();
+                units.insert(key.clone(), u);
+                unitkeys.push(key);
+                u
+            }
+        };
+        let p = match params.get(par) {
+            Some(p) => *p,
+            None => {
+                let p = parvals.len();
+                params.insert(par.to_string(), p);
+                parvals.push((par.to_string(), vec![]));
+                p
+            }
+        };
+        let vals = &mut parvals[p].1;
+        if vals.len() <= u {
+            vals.resize(u + 1, None);
+        }
+        if vals[u].is_some() {
+            duplicates += 1;
+        }
+        let v = record.get(valcol).unwrap_or("");
+        vals[u] = Some((v.to_string(), dialect.parse_f64(v)));
     }
+
+    if duplicates > 0 {
+        println!(
+            "{} duplicate unit/parameter values in CSV datafile '{}', the last ones are used.",
+            duplicates, datpath
+        );
+    }
+
+    let mut k = 0;
+    for (j, name) in qtableprops.long_keys.iter().enumerate() {
+        let mut data = Data::key(name, limitstable.get_filter(name, &qtableprops.filter));
+        for key in unitkeys.iter() {
+            data.push(&key[j]);
+        }
+        datatable.insert(k, data);
+        k += 1;
+    }
+    for (name, mut vals) in parvals.into_iter() {
+        vals.resize(unitkeys.len(), None);
+        let mut data = Data::new(&name, limitstable.get_filter(&name, &qtableprops.filter));
+        for v in vals.iter() {
+            match v {
+                Some((v, num)) => data.push_parsed(v, *num),
+                None => data.push(""),
+            }
+        }
+        datatable.insert(k, data);
+        k += 1;
+    }
+    Ok(())
 }
 
-pub fn create_limits_file(datpathstr: &String, sigdigits: usize) -> Result<(), String> {
+/// writes a limits file derived from the data (see OPT derive) next to the first data file or to outpath
+pub fn create_limits_file(
+    datpaths: &[String],
+    limpaths: &[String],
+    outpath: Option<&str>,
+    sigdigits: usize,
+    qtableprops: &QTableProps,
+) -> Result<(), String> {
+    let mut qtableprops = qtableprops.clone();
+    if qtableprops.filter == Filter::None {
+        qtableprops.filter = Filter::IQR(1.5);
+    }
+    if qtableprops.derive_keep_spec && limpaths.is_empty() {
+        return Err(
+            "HINT: derivekeepspec needs at least one CSV limits file to keep the spec limits from."
+                .to_string(),
+        );
+    }
+    let mut limitstable = LimitsTable::new();
+    for limpath in limpaths.iter() {
+        limitstable.read_limits(limpath, &qtableprops)?;
+    }
+    limitstable.resolve_targets()?;
     let mut datatable = DataTable::new();
-    let limitstable = LimitsTable::new();
-    datatable.add_data(&datpathstr, &limitstable, &Filter::IQR(1.5))?;
+    datatable.add_datafiles(datpaths, ParsedFiles::new(), &limitstable, &qtableprops)?;
+    limitstable.resolve(&|stat, par| column_statistic(&datatable, stat, par, &qtableprops))?;
 
-    let datpath = Path::new(datpathstr);
-    let mut pathbuf = PathBuf::new();
-    pathbuf.push(datpath.parent().unwrap());
-    pathbuf.push(datpath.file_stem().unwrap());
-    pathbuf.set_extension("lim.csv");
+    let groups = match qtableprops.derive_groups {
+        true if qtableprops.group_by.is_empty() => {
+            println!("derivegroups is set without groupby, limits are derived for all rows only.");
+            vec![]
+        }
+        true => Groups::new(&datatable, &qtableprops)?.groups,
+        false => vec![],
+    };
+    let keycount = groups.first().map(|g| g.keys.len()).unwrap_or(0);
+
+    let pathbuf = match outpath {
+        Some(outpath) => PathBuf::from(outpath),
+        None => {
+            let datpathstr = &datpaths[0];
+            let datpath = Path::new(file_path(datpathstr));
+            let mut pathbuf = PathBuf::new();
+            pathbuf.push(datpath.parent().unwrap());
+            pathbuf.push(datpath.file_stem().unwrap());
+            pathbuf.set_extension("lim.csv");
+            pathbuf
+        }
+    };
 
     let mut wtr = match Writer::from_path(&pathbuf) {
         Ok(wtr) => wtr,
         Err(e) => return Err(format!("{:?}", e)),
     };
 
-    match wtr.write_record(&[
-        "USE", "PAR", "LSL", "TGT", "USL", "LCL", "UCL", "", "<FIL", "TER>",
-    ]) {
+    let mut header: Vec<String> = vec!["USE".into(), "PAR".into(), "UNIT".into()];
+    for i in 0..keycount {
+        header.push(format!("KEY{}", i + 1));
+    }
+    for h in ["LSL", "TGT", "USL", "LCL", "UCL", "", "<FIL", "TER>"].iter() {
+        header.push(h.to_string());
+    }
+    match wtr.write_record(&header) {
         Ok(_) => (),
         Err(e) => return Err(format!("{:?}", e)),
     };
 
     for (_k, v) in datatable.iter() {
-        if v.vals.len() < 1 {
+        if v.is_empty() || v.kind != ColumnKind::Numeric || !v.selected {
             continue;
         }
-        let numbers = numbers::Numbers::new(&v.vals, std::f64::MAX, &v.filt);
-        let med = numbers.med();
-        let std = numbers.std();
-        match wtr.write_record(&[
-            "x",
-            &v.name,
-            &(med - 6.0 * std).frmtf64(sigdigits, ""),
-            &med.frmtf64(sigdigits, ""),
-            &(med + 6.0 * std).frmtf64(sigdigits, ""),
-            &(med - 3.0 * std).frmtf64(sigdigits, ""),
-            &(med + 3.0 * std).frmtf64(sigdigits, ""),
-            "",
-            "iqr",
-            "1.5",
-        ]) {
-            Ok(_) => (),
-            Err(e) => return Err(format!("{:?}", e)),
+        let row = limitstable.get_row(&v.name).map(|(_, row)| row);
+        let unit = match (v.unit.is_empty(), row) {
+            (true, Some(row)) => row.unit.clone(),
+            _ => v.unit.clone(),
         };
+
+        // the row for all values, then a keyed row for each group
+        let mut subsets: Vec<(Vec<String>, Vec<f64>, Option<Limits>)> = vec![(
+            vec![String::new(); keycount],
+            v.nums.clone(),
+            row.map(|row| row.limits.clone()),
+        )];
+        for g in groups.iter() {
+            let kept = row.map(|row| match row.keyed_index(&g.keys, None) {
+                Some(i) => row.keyed_limits(i).0,
+                None => row.limits.clone(),
+            });
+            subsets.push((
+                g.keys.iter().map(|(c, v)| format!("{}={}", c, v)).collect(),
+                v.select(&g.indices),
+                kept,
+            ));
+        }
+
+        for (keys, nums, kept) in subsets.iter() {
+            let numbers = numbers::Numbers::new(
+                nums,
+                f64::MAX,
+                &qtableprops.sentinels,
+                &v.filt,
+                qtableprops.quantile,
+            );
+            let mut limits = derive_limits(&numbers, &qtableprops.derive);
+            // spec limits set in the limits files are kept, missing ones are derived
+            if let (true, Some(kept)) = (qtableprops.derive_keep_spec, kept) {
+                for (i, key) in ["lsl", "tgt", "usl"].iter().enumerate() {
+                    match kept.get(*key) {
+                        Some(x) if !x.is_nan() => limits[i] = *x,
+                        _ => (),
+                    }
+                }
+            }
+            let (fil, ter) = v.filt.cells();
+
+            let mut record: Vec<String> = vec!["x".into(), v.name.clone(), unit.clone()];
+            record.extend(keys.iter().cloned());
+            record.extend(limits.iter().map(|x| x.frmtf64(sigdigits, "")));
+            record.extend(["".to_string(), fil, ter]);
+            match wtr.write_record(&record) {
+                Ok(_) => (),
+                Err(e) => return Err(format!("{:?}", e)),
+            };
+        }
     }
     match wtr.flush() {
         Ok(_) => (),
@@ -182,7 +1223,65 @@ pub fn create_limits_file(datpathstr: &String, sigdigits: usize) -> Result<(), S
 
     println!(
         "Limits file '{:?}' generated from '{}'.",
-        pathbuf, datpathstr
+        pathbuf,
+        datpaths.join("', '")
     );
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::read_config;
+    use crate::limits::LimitsRow;
+
+    fn column(name: &str, values: &[f64]) -> Data {
+        let mut data = Data::new(name, Filter::None);
+        for v in values {
+            data.push(&v.to_string());
+        }
+        data
+    }
+
+    #[test]
+    fn serial_numbers_are_ids_only_by_name_or_without_limits() {
+        let (qtableprops, _) = read_config(&"../data/sample.cfg.csv".to_string()).unwrap();
+        let counting: Vec<f64> = (1..=12).map(|x| x as f64).collect();
+        let drifting: Vec<f64> = (0..12).map(|x| 40.0 - 2.0 * x as f64).collect();
+        let mut limitstable = LimitsTable::new();
+        let kind =
+            |data: &Data, limitstable: &LimitsTable| data.classify(&qtableprops, limitstable);
+
+        // a monotonic integer measurement, e.g. a trim code, stays a parameter
+        assert_eq!(
+            kind(&column("TRIM", &drifting), &limitstable),
+            ColumnKind::Numeric
+        );
+        assert_eq!(
+            kind(&column("BIN", &counting), &limitstable),
+            ColumnKind::Numeric
+        );
+        assert_eq!(
+            kind(&column("Serial No", &counting), &limitstable),
+            ColumnKind::Id
+        );
+        assert_eq!(
+            kind(&column("PART_ID", &drifting), &limitstable),
+            ColumnKind::Id
+        );
+        assert_eq!(
+            kind(&column("BIN", &counting[..5]), &limitstable),
+            ColumnKind::Numeric
+        );
+
+        limitstable.insert("TRIM".to_string(), LimitsRow::default());
+        assert_eq!(
+            kind(&column("TRIM", &drifting), &limitstable),
+            ColumnKind::Numeric
+        );
+        assert_eq!(
+            kind(&column("BIN", &counting), &limitstable),
+            ColumnKind::Id
+        );
+    }
+}
@@ -0,0 +1,204 @@
+//! derivation of limits from the data for the limits command
+
+use crate::numbers::Numbers;
+
+/// how the limits command derives limits from the data, TGT is always the median
+#[derive(Debug, Clone, PartialEq)]
+pub enum Derive {
+    /// median -/+ k x sdev for spec and control limits
+    Sigma(f64, f64),
+    /// lower percent of the spec and control limits, the upper ones are 100 - percent
+    Percentile(f64, f64),
+    /// normal tolerance intervals covering percent for spec and control limits, with percent confidence
+    Tolerance(f64, f64, f64),
+}
+
+impl Default for Derive {
+    fn default() -> Self {
+        Derive::Sigma(6.0, 3.0)
+    }
+}
+
+/// lsl, tgt, usl, lcl, ucl derived from the numbers
+pub fn derive_limits(numbers: &Numbers, derive: &Derive) -> [f64; 5] {
+    let med = numbers.med();
+    let std = numbers.std();
+    let (spec, ctrl) = match derive {
+        Derive::Sigma(s, c) => (
+            (med - s * std, med + s * std),
+            (med - c * std, med + c * std),
+        ),
+        Derive::Percentile(s, c) => (
+            (numbers.prc(s / 100.0), numbers.prc(1.0 - s / 100.0)),
+            (numbers.prc(c / 100.0), numbers.prc(1.0 - c / 100.0)),
+        ),
+        Derive::Tolerance(s, c, confidence) => {
+            let mea = numbers.mea();
+            let ks = tolerance_factor(numbers.cnt(), *s, *confidence);
+            let kc = tolerance_factor(numbers.cnt(), *c, *confidence);
+            (
+                (mea - ks * std, mea + ks * std),
+                (mea - kc * std, mea + kc * std),
+            )
+        }
+    };
+    [spec.0, med, spec.1, ctrl.0, ctrl.1]
+}
+
+/// two-sided normal tolerance factor (Howe) for n values covering percent with percent confidence
+pub fn tolerance_factor(n: f64, coverage: f64, confidence: f64) -> f64 {
+    if n < 2.0 || !(0.0..100.0).contains(&coverage) || !(0.0..100.0).contains(&confidence) {
+        return f64::NAN;
+    }
+    let z = normal_quantile(0.5 + coverage / 200.0);
+    let chi2 = chi2_quantile(1.0 - confidence / 100.0, n - 1.0);
+    z * ((n - 1.0) * (1.0 + 1.0 / n) / chi2).sqrt()
+}
+
+/// quantile of the chi-square distribution with dof degrees of freedom (Wilson-Hilferty)
+fn chi2_quantile(p: f64, dof: f64) -> f64 {
+    let h = 2.0 / (9.0 * dof);
+    dof * (1.0 - h + normal_quantile(p) * h.sqrt()).powi(3).max(0.0)
+}
+
+/// quantile of the standard normal distribution (Acklam, relative error < 1.2e-9)
+pub fn normal_quantile(p: f64) -> f64 {
+    if p <= 0.0 || p >= 1.0 {
+        return f64::NAN;
+    }
+    let a = [
+        -3.969683028665376e+01,
+        2.209460984245205e+02,
+        -2.759285104469687e+02,
+        1.38357751867269e+02,
+        -3.066479806614716e+01,
+        2.506628277459239e+00,
+    ];
+    let b = [
+        -5.447609879822406e+01,
+        1.615858368580409e+02,
+        -1.556989798598866e+02,
+        6.680131188771972e+01,
+        -1.328068155288572e+01,
+    ];
+    let c = [
+        -7.784894002430293e-03,
+        -3.223964580411365e-01,
+        -2.400758277161838e+00,
+        -2.549732539343734e+00,
+        4.374664141464968e+00,
+        2.938163982698783e+00,
+    ];
+    let d = [
+        7.784695709041462e-03,
+        3.224671290700398e-01,
+        2.445134137142996e+00,
+        3.754408661907416e+00,
+    ];
+    let tail = |q: f64| {
+        (((((c[0] * q + c[1]) * q + c[2]) * q + c[3]) * q + c[4]) * q + c[5])
+            / ((((d[0] * q + d[1]) * q + d[2]) * q + d[3]) * q + 1.0)
+    };
+    let p_low = 0.02425;
+    if p < p_low {
+        tail((-2.0 * p.ln()).sqrt())
+    } else if p > 1.0 - p_low {
+        -tail((-2.0 * (1.0 - p).ln()).sqrt())
+    } else {
+        let q = p - 0.5;
+        let r = q * q;
+        (((((a[0] * r + a[1]) * r + a[2]) * r + a[3]) * r + a[4]) * r + a[5]) * q
+            / (((((b[0] * r + b[1]) * r + b[2]) * r + b[3]) * r + b[4]) * r + 1.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::numbers::Quantile;
+    use crate::qtable::Filter;
+
+    fn close(x: f64, expected: f64, rel: f64) -> bool {
+        ((x - expected) / expected).abs() < rel
+    }
+
+    /// 0, 1, .., 100
+    fn numbers() -> Numbers {
+        let nums: Vec<f64> = (0..=100).map(|x| x as f64).collect();
+        Numbers::new(&nums, f64::MAX, &[], &Filter::None, Quantile::default())
+    }
+
+    #[test]
+    fn sigma() {
+        let numbers = numbers();
+        let std = numbers.std();
+        let [lsl, tgt, usl, lcl, ucl] = derive_limits(&numbers, &Derive::Sigma(6.0, 3.0));
+        assert_eq!(tgt, 50.0);
+        assert!(close(lsl, 50.0 - 6.0 * std, 1e-12) && close(usl, 50.0 + 6.0 * std, 1e-12));
+        assert!(close(lcl, 50.0 - 3.0 * std, 1e-12) && close(ucl, 50.0 + 3.0 * std, 1e-12));
+    }
+
+    #[test]
+    fn percentile() {
+        let limits = derive_limits(&numbers(), &Derive::Percentile(5.0, 10.0));
+        assert_eq!(limits, [5.0, 50.0, 95.0, 10.0, 90.0]);
+    }
+
+    #[test]
+    fn tolerance() {
+        let numbers = numbers();
+        let (mea, std) = (numbers.mea(), numbers.std());
+        let k = tolerance_factor(101.0, 99.0, 95.0);
+        let limits = derive_limits(&numbers, &Derive::Tolerance(99.0, 95.0, 95.0));
+        assert!(close(limits[0], mea - k * std, 1e-12) && close(limits[2], mea + k * std, 1e-12));
+        assert!(limits[3] > limits[0] && limits[4] < limits[2]);
+    }
+
+    #[test]
+    fn normal_quantiles() {
+        for (p, z) in [
+            (0.5, 0.0),
+            (0.841344746, 1.0),
+            (0.975, 1.959963985),
+            (0.995, 2.575829304),
+            (0.00135, -2.999977),
+        ] {
+            assert!((normal_quantile(p) - z).abs() < 1e-6, "{} {}", p, z);
+        }
+        assert!(normal_quantile(0.0).is_nan() && normal_quantile(1.0).is_nan());
+    }
+
+    #[test]
+    fn wilson_hilferty_chi2() {
+        // chi-square quantiles of statistical tables
+        for (p, dof, chi2) in [
+            (0.05, 9.0, 3.32511),
+            (0.95, 9.0, 16.9190),
+            (0.05, 19.0, 10.1170),
+            (0.01, 29.0, 14.2565),
+            (0.05, 100.0, 77.9295),
+        ] {
+            assert!(close(chi2_quantile(p, dof), chi2, 0.005), "{} {}", p, dof);
+        }
+    }
+
+    #[test]
+    fn howe_tolerance_factor() {
+        // two-sided factors of tolerance interval tables (n, coverage, confidence)
+        for (n, coverage, confidence, k) in [
+            (10.0, 95.0, 95.0, 3.379),
+            (10.0, 99.0, 95.0, 4.433),
+            (20.0, 95.0, 95.0, 2.752),
+        ] {
+            assert!(
+                close(tolerance_factor(n, coverage, confidence), k, 0.005),
+                "{} {} {}",
+                n,
+                coverage,
+                confidence
+            );
+        }
+        assert!(tolerance_factor(1.0, 95.0, 95.0).is_nan());
+        assert!(tolerance_factor(10.0, 100.0, 95.0).is_nan());
+    }
+}
@@ -0,0 +1,279 @@
+//! CSV dialect types and methods
+extern crate csv;
+extern crate encoding_rs;
+extern crate encoding_rs_io;
+
+use crate::workbook::{is_workbook, sheet_to_csv};
+use csv::{Reader, ReaderBuilder};
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8, WINDOWS_1252};
+use encoding_rs_io::DecodeReaderBytesBuilder;
+use std::fs::File;
+use std::io::{Cursor, Read, Seek, SeekFrom};
+
+/// bytes read from the start of a file to detect encoding and delimiter
+const SNIFF_LEN: usize = 64 * 1024;
+
+/// delimiters tried by auto detection, the first one is the fallback
+const DELIMITERS: [u8; 4] = [b',', b';', b'\t', b'|'];
+
+/// how CSV files are written: separators, quoting and encoding.
+/// `None` means auto detection per file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Dialect {
+    pub delimiter: Option<u8>,
+    pub quote: u8,
+    pub decimal: Option<char>,
+    pub thousands: Option<char>,
+    pub encoding: Option<&'static Encoding>,
+}
+
+impl Default for Dialect {
+    fn default() -> Self {
+        Dialect {
+            delimiter: None,
+            quote: b'"',
+            decimal: None,
+            thousands: None,
+            encoding: None,
+        }
+    }
+}
+
+impl Dialect {
+    /// parse a number written in this dialect
+    pub fn parse_f64(&self, s: &str) -> Option<f64> {
+        let s = s.trim();
+        if self.decimal.unwrap_or('.') == '.' && self.thousands.is_none() {
+            return s.parse::<f64>().ok();
+        }
+        let mut t = String::with_capacity(s.len());
+        for c in s.chars() {
+            if Some(c) == self.thousands {
+                continue;
+            }
+            if Some(c) == self.decimal {
+                t.push('.');
+            } else {
+                t.push(c);
+            }
+        }
+        t.parse::<f64>().ok()
+    }
+
+    /// rewrite a number of this dialect with decimal point and without thousands separators,
+    /// anything else is returned unchanged
+    pub fn normalize(&self, s: &str) -> String {
+        if self.decimal.unwrap_or('.') == '.' && self.thousands.is_none() {
+            return s.to_string();
+        }
+        match self.parse_f64(s) {
+            Some(_) => s
+                .trim()
+                .chars()
+                .filter(|c| Some(*c) != self.thousands)
+                .map(|c| if Some(c) == self.decimal { '.' } else { c })
+                .collect(),
+            None => s.to_string(),
+        }
+    }
+}
+
+/// open a CSV file as reader with encoding and delimiter resolved by the dialect.
+/// Returns the reader and the dialect with all auto settings resolved.
+/// A sheet of a workbook (`book.xlsx#Sheet`) is read as CSV with decimal point.
+pub fn open_reader(
+    path: &str,
+    dialect: &Dialect,
+) -> Result<(Reader<Box<dyn Read>>, Dialect), String> {
+    if is_workbook(path) {
+        let bytes = sheet_to_csv(path)?;
+        let resolved = Dialect {
+            delimiter: Some(b','),
+            quote: b'"',
+            decimal: Some('.'),
+            thousands: None,
+            encoding: Some(UTF_8),
+        };
+        let rdr = ReaderBuilder::new().from_reader(Box::new(Cursor::new(bytes)) as Box<dyn Read>);
+        return Ok((rdr, resolved));
+    }
+
+    let mut file = match File::open(path) {
+        Err(e) => return Err(format!("{}", e)),
+        Ok(file) => file,
+    };
+
+    let mut head = vec![];
+    if let Err(e) = file.by_ref().take(SNIFF_LEN as u64).read_to_end(&mut head) {
+        return Err(format!("{}", e));
+    }
+    if let Err(e) = file.seek(SeekFrom::Start(0)) {
+        return Err(format!("{}", e));
+    }
+
+    let mut resolved = dialect.clone();
+
+    let encoding = match (Encoding::for_bom(&head), dialect.encoding) {
+        (Some((bom, _)), _) => bom,
+        (None, Some(e)) => e,
+        (None, None) => sniff_encoding(&head),
+    };
+    resolved.encoding = Some(encoding);
+
+    if resolved.delimiter.is_none() {
+        let (text, _, _) = encoding.decode(&head);
+        let line = text.lines().next().unwrap_or("");
+        resolved.delimiter = Some(sniff_delimiter(line, dialect.quote));
+    }
+
+    if resolved.decimal.is_none() {
+        resolved.decimal = match resolved.delimiter {
+            Some(b',') => Some('.'),
+            Some(delimiter) => {
+                let (text, _, _) = encoding.decode(&head);
+                match sniff_decimal(&text, delimiter) {
+                    (Some(decimal), thousands, _) => {
+                        if resolved.thousands.is_none() {
+                            resolved.thousands = thousands;
+                        }
+                        Some(decimal)
+                    }
+                    (None, _, ambiguous) => {
+                        if ambiguous {
+                            println!(
+                                "decimal separator of {} is ambiguous (e.g. 1,234), decimal comma assumed, set the config OPT 'decimal'",
+                                path
+                            );
+                        }
+                        Some(',')
+                    }
+                }
+            }
+            None => Some('.'),
+        };
+    }
+
+    let decoder = DecodeReaderBytesBuilder::new()
+        .encoding(Some(encoding))
+        .build(file);
+
+    let rdr = ReaderBuilder::new()
+        .delimiter(resolved.delimiter.unwrap())
+        .quote(resolved.quote)
+        .from_reader(Box::new(decoder) as Box<dyn Read>);
+
+    Ok((rdr, resolved))
+}
+
+/// read a whole text file decoded by BOM, the dialect encoding or auto detection
+pub fn read_to_string(path: &str, dialect: &Dialect) -> Result<String, String> {
+    let bytes = match std::fs::read(path) {
+        Err(e) => return Err(format!("{}", e)),
+        Ok(bytes) => bytes,
+    };
+    let encoding = match (Encoding::for_bom(&bytes), dialect.encoding) {
+        (Some((bom, _)), _) => bom,
+        (None, Some(e)) => e,
+        (None, None) => sniff_encoding(&bytes[..bytes.len().min(SNIFF_LEN)]),
+    };
+    let (text, _, _) = encoding.decode(&bytes);
+    Ok(text.into_owned())
+}
+
+/// guess the encoding of a file start without BOM
+fn sniff_encoding(head: &[u8]) -> &'static Encoding {
+    // UTF-16 without BOM: every second byte of ASCII text is zero
+    let even_zeros = head.iter().step_by(2).filter(|b| **b == 0).count();
+    let odd_zeros = head.iter().skip(1).step_by(2).filter(|b| **b == 0).count();
+    let half = head.len() / 2;
+    if half > 0 && odd_zeros > half * 3 / 4 {
+        return UTF_16LE;
+    }
+    if half > 0 && even_zeros > half * 3 / 4 {
+        return UTF_16BE;
+    }
+    match std::str::from_utf8(head) {
+        Ok(_) => UTF_8,
+        // a multibyte character cut off at the end of the sniffed bytes is still UTF-8
+        Err(e) if e.error_len().is_none() => UTF_8,
+        Err(_) => WINDOWS_1252,
+    }
+}
+
+/// guess the delimiter from the header line, quoted parts are skipped
+fn sniff_delimiter(line: &str, quote: u8) -> u8 {
+    let mut counts = [0; DELIMITERS.len()];
+    let mut quoted = false;
+    for b in line.bytes() {
+        if b == quote {
+            quoted = !quoted;
+            continue;
+        }
+        if quoted {
+            continue;
+        }
+        if let Some(i) = DELIMITERS.iter().position(|d| *d == b) {
+            counts[i] += 1;
+        }
+    }
+    let mut best = 0;
+    for (i, c) in counts.iter().enumerate() {
+        if *c > counts[best] {
+            best = i;
+        }
+    }
+    DELIMITERS[best]
+}
+
+/// guess the decimal separator from the numbers after the header line:
+/// the last of '.' and ',' in a number is the decimal separator, a separator found twice groups thousands.
+/// Returns the decimal separator (None if undecided), the other separator if it grouped thousands
+/// and true if only numbers like 1,234 were found, which can be read either way.
+fn sniff_decimal(text: &str, delimiter: u8) -> (Option<char>, Option<char>, bool) {
+    // decimal votes and thousands seen for '.' and ','
+    let mut decimal = [0, 0];
+    let mut grouping = [false, false];
+    let index = |c: char| if c == '.' { 0 } else { 1 };
+    for line in text.lines().skip(1) {
+        for cell in line.split(delimiter as char) {
+            let cell = cell.trim().trim_matches('"');
+            let cell = cell.trim_start_matches(['-', '+']);
+            if !cell.starts_with(|c: char| c.is_ascii_digit())
+                || !cell
+                    .chars()
+                    .all(|c| c.is_ascii_digit() || c == '.' || c == ',')
+            {
+                continue;
+            }
+            let last = match cell.rfind(['.', ',']) {
+                Some(last) => last,
+                None => continue,
+            };
+            let sep = cell.as_bytes()[last] as char;
+            let other = if sep == '.' { ',' } else { '.' };
+            if cell.contains(other) {
+                decimal[index(sep)] += 1;
+                grouping[index(other)] = true;
+            } else if cell.matches(sep).count() > 1 {
+                decimal[index(other)] += 1;
+                grouping[index(sep)] = true;
+            } else if cell.len() - last - 1 == 3 && last <= 3 && !cell.starts_with('0') {
+                grouping[index(sep)] = true;
+            } else {
+                decimal[index(sep)] += 1;
+            }
+        }
+    }
+    if decimal[0] > decimal[1] {
+        (Some('.'), Some(',').filter(|_| grouping[1]), false)
+    } else if decimal[1] > decimal[0] {
+        (Some(','), Some('.').filter(|_| grouping[0]), false)
+    } else {
+        (None, None, grouping[0] || grouping[1])
+    }
+}
+
+/// encoding by label like utf-8, latin1, windows-1252, utf-16le, utf-16be
+pub fn encoding_for_label(label: &str) -> Option<&'static Encoding> {
+    Encoding::for_label(label.trim().as_bytes())
+}
@@ -0,0 +1,421 @@
+//! expressions in the limit cells of a limits file, e.g. `tgt*0.95`, `tgt-0.2`, `%5` or `1.1*mean(PARAM_07)`
+
+use crate::dialect::Dialect;
+use crate::numbers::Numbers;
+
+/// statistic of a parameter of the data used in an expression
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Stat {
+    Mean,
+    Median,
+    Sdev,
+    Min,
+    Max,
+}
+
+impl Stat {
+    /// the statistic of the numbers
+    pub fn of(&self, numbers: &Numbers) -> f64 {
+        match self {
+            Stat::Mean => numbers.mea(),
+            Stat::Median => numbers.med(),
+            Stat::Sdev => numbers.std(),
+            Stat::Min => numbers.min(),
+            Stat::Max => numbers.max(),
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Stat> {
+        match name {
+            "mean" => Some(Stat::Mean),
+            "median" => Some(Stat::Median),
+            "sdev" => Some(Stat::Sdev),
+            "min" => Some(Stat::Min),
+            "max" => Some(Stat::Max),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Number(f64),
+    /// TGT of the row
+    Tgt,
+    /// TGT -/+ percent of |TGT|, minus for the lower limits
+    Percent(f64),
+    Stat(Stat, String),
+    Neg(Box<Expr>),
+    Binary(char, Box<Expr>, Box<Expr>),
+}
+
+/// names allowed in expressions, for error messages
+const ALLOWED: &str =
+    "numbers, + - * / ( ), tgt, %N, mean(PAR), median(PAR), sdev(PAR), min(PAR), max(PAR)";
+
+impl Expr {
+    /// parse an expression with numbers written in the dialect
+    pub fn parse(s: &str, dialect: &Dialect) -> Result<Expr, String> {
+        let s = s.trim();
+        if let Some(p) = s.strip_prefix('%') {
+            return match dialect.parse_f64(p) {
+                Some(p) => Ok(Expr::Percent(p)),
+                None => Err(format!("'{}' after % is not a number", p.trim())),
+            };
+        }
+        let mut parser = Parser {
+            chars: s.chars().collect(),
+            pos: 0,
+            dialect,
+        };
+        let expr = parser.sum()?;
+        parser.skip_spaces();
+        match parser.peek() {
+            None => Ok(expr),
+            Some(c) => Err(format!(
+                "unexpected '{}' at position {}, allowed: {}",
+                c,
+                parser.pos + 1,
+                ALLOWED
+            )),
+        }
+    }
+
+    /// true, if statistics of the data are needed to evaluate the expression
+    pub fn uses_data(&self) -> bool {
+        match self {
+            Expr::Stat(_, _) => true,
+            Expr::Neg(e) => e.uses_data(),
+            Expr::Binary(_, a, b) => a.uses_data() || b.uses_data(),
+            _ => false,
+        }
+    }
+
+    /// value of the expression for the limit (lsl, usl, lcl, ucl) with the target of the row,
+    /// stat gives the statistic of a parameter of the data, None if the parameter is unknown
+    pub fn eval(
+        &self,
+        limit: &str,
+        tgt: f64,
+        stat: &dyn Fn(Stat, &str) -> Option<f64>,
+    ) -> Result<f64, String> {
+        let target = || match tgt.is_nan() {
+            true => Err("TGT of the row is not set".to_string()),
+            false => Ok(tgt),
+        };
+        match self {
+            Expr::Number(x) => Ok(*x),
+            Expr::Tgt => target(),
+            Expr::Percent(p) => {
+                let delta = target()?.abs() * p / 100.0;
+                match limit {
+                    "lsl" | "lcl" => Ok(tgt - delta),
+                    _ => Ok(tgt + delta),
+                }
+            }
+            Expr::Stat(s, par) => match stat(*s, par) {
+                Some(x) => Ok(x),
+                None => Err(format!("parameter '{}' is not in the data", par)),
+            },
+            Expr::Neg(e) => Ok(-e.eval(limit, tgt, stat)?),
+            Expr::Binary(op, a, b) => {
+                let a = a.eval(limit, tgt, stat)?;
+                let b = b.eval(limit, tgt, stat)?;
+                match op {
+                    '+' => Ok(a + b),
+                    '-' => Ok(a - b),
+                    '*' => Ok(a * b),
+                    _ if b == 0.0 => Err("division by zero".to_string()),
+                    _ => Ok(a / b),
+                }
+            }
+        }
+    }
+}
+
+struct Parser<'a> {
+    chars: Vec<char>,
+    pos: usize,
+    dialect: &'a Dialect,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn skip_spaces(&mut self) {
+        while self.peek().map(|c| c.is_whitespace()).unwrap_or(false) {
+            self.pos += 1;
+        }
+    }
+
+    /// term (+|- term)*
+    fn sum(&mut self) -> Result<Expr, String> {
+        let mut expr = self.product()?;
+        loop {
+            self.skip_spaces();
+            match self.peek() {
+                Some(op) if op == '+' || op == '-' => {
+                    self.pos += 1;
+                    expr = Expr::Binary(op, Box::new(expr), Box::new(self.product()?));
+                }
+                _ => return Ok(expr),
+            }
+        }
+    }
+
+    /// factor (*|/ factor)*
+    fn product(&mut self) -> Result<Expr, String> {
+        let mut expr = self.factor()?;
+        loop {
+            self.skip_spaces();
+            match self.peek() {
+                Some(op) if op == '*' || op == '/' => {
+                    self.pos += 1;
+                    expr = Expr::Binary(op, Box::new(expr), Box::new(self.factor()?));
+                }
+                _ => return Ok(expr),
+            }
+        }
+    }
+
+    /// -factor, (sum), number, tgt or stat(PAR)
+    fn factor(&mut self) -> Result<Expr, String> {
+        self.skip_spaces();
+        let start = self.pos;
+        match self.peek() {
+            None => Err("expression ends too early".to_string()),
+            Some('-') => {
+                self.pos += 1;
+                Ok(Expr::Neg(Box::new(self.factor()?)))
+            }
+            Some('(') => {
+                self.pos += 1;
+                let expr = self.sum()?;
+                self.skip_spaces();
+                match self.peek() {
+                    Some(')') => {
+                        self.pos += 1;
+                        Ok(expr)
+                    }
+                    _ => Err(format!("missing ')' for '(' at position {}", start + 1)),
+                }
+            }
+            Some(c) if c.is_ascii_digit() || c == '.' || Some(c) == self.dialect.decimal => {
+                while let Some(c) = self.peek() {
+                    let exponent_sign = (c == '+' || c == '-')
+                        && matches!(self.chars.get(self.pos - 1), Some('e') | Some('E'));
+                    if c.is_ascii_digit()
+                        || c == '.'
+                        || c == 'e'
+                        || c == 'E'
+                        || exponent_sign
+                        || Some(c) == self.dialect.decimal
+                        || Some(c) == self.dialect.thousands
+                    {
+                        self.pos += 1;
+                    } else {
+                        break;
+                    }
+                }
+                let number: String = self.chars[start..self.pos].iter().collect();
+                match self.dialect.parse_f64(&number) {
+                    Some(x) => Ok(Expr::Number(x)),
+                    None => Err(format!(
+                        "'{}' at position {} is not a number",
+                        number,
+                        start + 1
+                    )),
+                }
+            }
+            Some(c) if c.is_alphabetic() => {
+                while self
+                    .peek()
+                    .map(|c| c.is_alphanumeric() || c == '_')
+                    .unwrap_or(false)
+                {
+                    self.pos += 1;
+                }
+                let name: String = self.chars[start..self.pos]
+                    .iter()
+                    .collect::<String>()
+                    .to_lowercase();
+                if name == "tgt" {
+                    return Ok(Expr::Tgt);
+                }
+                let stat = match Stat::from_name(&name) {
+                    Some(stat) => stat,
+                    None => return Err(format!("unknown name '{}', allowed: {}", name, ALLOWED)),
+                };
+                self.skip_spaces();
+                if self.peek() != Some('(') {
+                    return Err(format!("'{}' needs a parameter like {}(PAR)", name, name));
+                }
+                // the parameter name may contain parentheses, e.g. I(VDD)
+                let open = self.pos;
+                let mut depth = 0;
+                while let Some(c) = self.peek() {
+                    self.pos += 1;
+                    match c {
+                        '(' => depth += 1,
+                        ')' if depth == 1 => {
+                            let par: String = self.chars[open + 1..self.pos - 1].iter().collect();
+                            return Ok(Expr::Stat(stat, par.trim().to_string()));
+                        }
+                        ')' => depth -= 1,
+                        _ => (),
+                    }
+                }
+                Err(format!("missing ')' for '(' at position {}", open + 1))
+            }
+            Some(c) => Err(format!(
+                "unexpected '{}' at position {}, allowed: {}",
+                c,
+                start + 1,
+                ALLOWED
+            )),
+        }
+    }
+}
+
+/// limit given by an expression, evaluated with the TGT of the merged limits files
+/// and, if it uses statistics of the data, when the data is read
+#[derive(Debug, Clone, PartialEq)]
+pub struct PendingLimit {
+    /// lsl, usl, lcl or ucl
+    pub limit: String,
+    pub expr: Expr,
+    /// the cell as written, for error messages
+    pub cell: String,
+    /// PAR and limits file of the row, for error messages
+    pub origin: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval(s: &str, limit: &str, tgt: f64) -> Result<f64, String> {
+        let stat = |stat: Stat, par: &str| match (stat, par) {
+            (Stat::Mean, "I(VDD)") => Some(2.0),
+            (Stat::Sdev, "PARAM_07") => Some(0.5),
+            _ => None,
+        };
+        Expr::parse(s, &Dialect::default())?.eval(limit, tgt, &stat)
+    }
+
+    fn error(s: &str) -> String {
+        Expr::parse(s, &Dialect::default()).unwrap_err()
+    }
+
+    #[test]
+    fn precedence() {
+        assert_eq!(eval("1+2*3", "lsl", 0.0), Ok(7.0));
+        assert_eq!(eval("(1+2)*3", "lsl", 0.0), Ok(9.0));
+        assert_eq!(eval("8-4-2", "lsl", 0.0), Ok(2.0));
+        assert_eq!(eval("8/4/2", "lsl", 0.0), Ok(1.0));
+        assert_eq!(eval("tgt - 0.2 * 2", "usl", 10.0), Ok(9.6));
+        assert_eq!(eval("1.5e2+1", "usl", 0.0), Ok(151.0));
+        assert_eq!(eval("1e-1*10", "usl", 0.0), Ok(1.0));
+    }
+
+    #[test]
+    fn unary_minus() {
+        assert_eq!(eval("-2", "lsl", 0.0), Ok(-2.0));
+        assert_eq!(eval("--2", "lsl", 0.0), Ok(2.0));
+        assert_eq!(eval("-tgt*2", "lsl", 3.0), Ok(-6.0));
+        assert_eq!(eval("3*-2", "lsl", 0.0), Ok(-6.0));
+        assert_eq!(eval("-(1+2)", "lsl", 0.0), Ok(-3.0));
+    }
+
+    #[test]
+    fn percent_of_target() {
+        for (limit, expected) in [("lsl", 95.0), ("lcl", 95.0), ("usl", 105.0), ("ucl", 105.0)] {
+            assert_eq!(eval("%5", limit, 100.0), Ok(expected));
+        }
+        // percent of |TGT|, the lower limits stay below a negative TGT
+        assert_eq!(eval("%10", "lsl", -20.0), Ok(-22.0));
+        assert_eq!(eval("%10", "usl", -20.0), Ok(-18.0));
+        assert_eq!(eval(" %5 ", "usl", 100.0), Ok(105.0));
+    }
+
+    #[test]
+    fn statistics() {
+        assert_eq!(
+            Expr::parse("1.1*mean(I(VDD))", &Dialect::default()),
+            Ok(Expr::Binary(
+                '*',
+                Box::new(Expr::Number(1.1)),
+                Box::new(Expr::Stat(Stat::Mean, "I(VDD)".to_string()))
+            ))
+        );
+        assert_eq!(eval("mean( I(VDD) ) + 1", "usl", f64::NAN), Ok(3.0));
+        assert_eq!(eval("MEAN(I(VDD))", "usl", f64::NAN), Ok(2.0));
+        assert_eq!(eval("tgt+3*sdev(PARAM_07)", "ucl", 10.0), Ok(11.5));
+        assert!(Expr::parse("mean(A)-tgt", &Dialect::default())
+            .unwrap()
+            .uses_data());
+        assert!(!Expr::parse("-tgt*2", &Dialect::default())
+            .unwrap()
+            .uses_data());
+    }
+
+    #[test]
+    fn decimal_comma() {
+        let dialect = Dialect {
+            decimal: Some(','),
+            thousands: Some('.'),
+            ..Default::default()
+        };
+        let stat = |_: Stat, _: &str| None;
+        let eval = |s: &str| Expr::parse(s, &dialect)?.eval("lsl", 100.0, &stat);
+        assert_eq!(eval("tgt*0,95"), Ok(95.0));
+        assert_eq!(eval("1.000,5-0,5"), Ok(1000.0));
+        assert_eq!(eval("%2,5"), Ok(97.5));
+        assert_eq!(eval(",5*2"), Ok(1.0));
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!(error("%x"), "'x' after % is not a number");
+        assert_eq!(
+            error("1+2)"),
+            format!("unexpected ')' at position 4, allowed: {}", ALLOWED)
+        );
+        assert_eq!(
+            error("1+#"),
+            format!("unexpected '#' at position 3, allowed: {}", ALLOWED)
+        );
+        assert_eq!(error("1+"), "expression ends too early");
+        assert_eq!(error(""), "expression ends too early");
+        assert_eq!(error("2*(1+2"), "missing ')' for '(' at position 3");
+        assert_eq!(error("mean(I(VDD)"), "missing ')' for '(' at position 5");
+        assert_eq!(error("1.2.3"), "'1.2.3' at position 1 is not a number");
+        assert_eq!(
+            error("avg(A)"),
+            format!("unknown name 'avg', allowed: {}", ALLOWED)
+        );
+        assert_eq!(error("mean A"), "'mean' needs a parameter like mean(PAR)");
+    }
+
+    #[test]
+    fn eval_errors() {
+        assert_eq!(
+            eval("tgt*0.9", "lsl", f64::NAN),
+            Err("TGT of the row is not set".to_string())
+        );
+        assert_eq!(
+            eval("%5", "usl", f64::NAN),
+            Err("TGT of the row is not set".to_string())
+        );
+        assert_eq!(
+            eval("mean(NOPE)", "usl", 1.0),
+            Err("parameter 'NOPE' is not in the data".to_string())
+        );
+        assert_eq!(
+            eval("1/(tgt-1)", "usl", 1.0),
+            Err("division by zero".to_string())
+        );
+    }
+}
@@ -12,6 +12,8 @@ pub enum GroupBy {
 pub struct Group {
     pub(crate) name: String,
     pub(crate) group: String,
+    /// (column, value) pairs of the groupby columns
+    pub(crate) keys: Vec<(String, String)>,
     pub(crate) indices: Vec<usize>,
 }
 
@@ -20,88 +22,110 @@ pub struct Groups {
     pub groups: Vec<Group>,
 }
 
-impl Groups {
-    pub fn new(datatable: &DataTable, qtableprops: &QTableProps) -> Result<Groups, String> {
-        let mut group_by_colnumber = vec![];
-        let mut datheaders: HashMap<String, usize> = HashMap::new();
-        for (k, v) in datatable.iter() {
-            datheaders.insert(v.name.clone(), k.clone());
-        }
+/// indices (starting from 0) of the groupby columns in the column names of the data
+pub(crate) fn group_columns(
+    names: &[String],
+    qtableprops: &QTableProps,
+) -> Result<Vec<usize>, String> {
+    let mut group_by_colnumber = vec![];
+    let mut datheaders: HashMap<String, usize> = HashMap::new();
+    for (k, v) in names.iter().enumerate() {
+        datheaders.insert(v.clone(), k);
+    }
 
-        for g in qtableprops.group_by.iter() {
-            match g {
-                GroupBy::ColNumber(u) => {
-                    if !group_by_colnumber.contains(u) {
-                        group_by_colnumber.push(*u);
-                    }
+    for g in qtableprops.group_by.iter() {
+        match g {
+            GroupBy::ColNumber(u) => {
+                if !group_by_colnumber.contains(u) {
+                    group_by_colnumber.push(*u);
                 }
-                GroupBy::ColName(p) => match datheaders.contains_key(p) {
-                    true => {
-                        let u = *datheaders.get(p).unwrap() + 1;
-                        if !group_by_colnumber.contains(&(u)) {
-                            group_by_colnumber.push(u)
-                        }
-                    }
-                    false => {
-                        return Err(format!("groupby column '{}' not found in datafile.", p));
-                    }
-                },
             }
+            GroupBy::ColName(p) => match datheaders.contains_key(p) {
+                true => {
+                    let u = *datheaders.get(p).unwrap() + 1;
+                    if !group_by_colnumber.contains(&(u)) {
+                        group_by_colnumber.push(u)
+                    }
+                }
+                false => {
+                    return Err(format!("groupby column '{}' not found in datafile.", p));
+                }
+            },
         }
+    }
+
+    let mut columns = vec![];
+    for g in group_by_colnumber.iter() {
+        if *g < 1 {
+            return Err(format!(
+                "group_by '{}' is lower than 1,
+       allowed is from 1 to length of number of columns of the data file.",
+                g
+            ));
+        }
+        let k = *g - 1;
+        if k + 1 > names.len() {
+            return Err(format!(
+                "group_by '{}' is greater than the numbers of parameters,
+       allowed is from 1 to length of number of columns of the data file.",
+                g
+            ));
+        }
+        columns.push(k);
+    }
+    Ok(columns)
+}
+
+/// name of the group i (starting from 0) in the number column
+pub(crate) fn group_name(i: usize) -> String {
+    format!["{number:>width$}>", number = i + 1, width = 2]
+}
+
+impl Groups {
+    pub fn new(datatable: &DataTable, qtableprops: &QTableProps) -> Result<Groups, String> {
+        let names: Vec<String> = datatable.values().map(|v| v.name.clone()).collect();
+        let group_columns = group_columns(&names, qtableprops)?;
 
         let mut groups: Vec<Group> = vec![];
         let mut groupsvec: Vec<String> = vec![];
 
-        for (i, g) in group_by_colnumber.iter().enumerate() {
-            if *g < 1 {
-                return Err(format!(
-                    "group_by '{}' is lower than 1,
-       allowed is from 1 to length of number of columns of the data file.",
-                    g
-                ));
-            }
-            let k = *g - 1;
-            if k > datatable.len() - 1 {
-                return Err(format!(
-                    "group_by '{}' is greater than the numbers of parameters,
-       allowed is from 1 to length of number of columns of the data file.",
-                    g
-                ));
-            }
-            let data = datatable.get(&k).unwrap();
+        for (i, k) in group_columns.iter().enumerate() {
+            let data = datatable.get(k).unwrap();
             if i == 0 {
-                for j in &data.vals {
-                    groupsvec.push(format!["{}={}", data.name, j]);
+                for j in 0..data.len() {
+                    groupsvec.push(format!["{}={}", data.name, data.value(j)]);
                 }
             } else {
-                for it in data.vals.iter().zip(groupsvec.iter_mut()) {
-                    let (ai, bi) = it;
-                    bi.push_str(format![", {}={}", data.name, ai].as_ref());
+                for (j, bi) in groupsvec.iter_mut().enumerate() {
+                    bi.push_str(format![", {}={}", data.name, data.value(j)].as_ref());
                 }
             }
         }
 
-        let mut splits = vec![];
-
-        for k in groupsvec.iter() {
-            if !splits.contains(k) {
-                splits.push(k.clone());
-            }
-        }
-
-        for (i, k) in splits.iter().enumerate() {
-            let mut indices: Vec<usize> = vec![];
-            for (m, n) in groupsvec.iter().enumerate() {
-                if n == k {
-                    indices.push(m);
+        // groups in order of first appearance, rows collected in one pass
+        let mut splits: HashMap<&String, usize> = HashMap::new();
+        for (m, n) in groupsvec.iter().enumerate() {
+            let i = match splits.get(n) {
+                Some(i) => *i,
+                None => {
+                    let i = groups.len();
+                    splits.insert(n, i);
+                    groups.push(Group {
+                        name: group_name(i),
+                        group: n.clone(),
+                        keys: group_columns
+                            .iter()
+                            .map(|k| {
+                                let data = datatable.get(k).unwrap();
+                                (data.name.clone(), data.value(m).trim().to_string())
+                            })
+                            .collect(),
+                        indices: vec![],
+                    });
+                    i
                 }
-            }
-
-            groups.push(Group {
-                name: format!["{number:>width$}>", number = i + 1, width = 2],
-                group: k.clone(),
-                indices,
-            });
+            };
+            groups[i].indices.push(m);
         }
 
         Ok(Groups { groups })
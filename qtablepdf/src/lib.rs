@@ -1,10 +1,21 @@
 //! lib qtablepdf helps to create tables with statistics from .csv data files
 pub mod config;
 pub mod data;
+pub mod derive;
+pub mod dialect;
+pub mod expr;
 pub mod group;
 pub mod limits;
 pub mod numbers;
 pub mod pdf;
+pub mod propose;
+pub mod qdas;
 pub mod qtable;
 pub mod sample;
+pub mod select;
+pub mod sketch;
+pub mod stdf;
+pub mod stream;
 pub mod table;
+pub mod time;
+pub mod workbook;
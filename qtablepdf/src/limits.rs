@@ -4,14 +4,18 @@ extern crate itertools_num;
 extern crate num;
 extern crate printpdf;
 
+use crate::config::QTableProps;
+use crate::data::{source_name, ParsedFile, ParsedFiles};
+use crate::dialect::open_reader;
+use crate::expr::{Expr, PendingLimit, Stat};
 use crate::numbers::{F64Ext, Numbers};
 
 use crate::qtable::{Filter, Mark};
-use csv::Reader;
+use crate::select::ParamPattern;
+use crate::time::{parse_time, parse_time_to, TimeWindow};
+use chrono::NaiveDateTime;
 use enumflags2::BitFlags;
 use std::collections::{BTreeMap, HashMap};
-use std::f64::NAN;
-use std::fs::File;
 
 /// limitscheck cases
 #[derive(Debug, Copy, Clone, PartialEq)]
@@ -20,6 +24,8 @@ pub enum YieldOk {
     SpecYieldNot,
     CtrlYieldNot,
     CpkNot,
+    /// too few values used for the statistics
+    Incomplete,
     NoLimits,
 }
 
@@ -34,7 +40,7 @@ pub trait LimitsExt<T> {
 impl LimitsExt<Limits> for Limits {
     fn getnum(&self, key: &str) -> f64 {
         if !self.contains_key(key) {
-            return NAN;
+            return f64::NAN;
         }
         self[key]
     }
@@ -43,6 +49,14 @@ impl LimitsExt<Limits> for Limits {
     }
 }
 
+/// threshold (cpk_min, specyld_min, ctrlyld_min) set for the parameter in the limits file, else the global one
+pub fn threshold(limits: &Limits, key: &str, global: f64) -> f64 {
+    match limits.get(key) {
+        Some(x) if !x.is_nan() => *x,
+        _ => global,
+    }
+}
+
 fn marker(yieldok: YieldOk, markit: bool) -> YieldOk {
     if markit {
         yieldok
@@ -51,12 +65,476 @@ fn marker(yieldok: YieldOk, markit: bool) -> YieldOk {
     }
 }
 
-/// lookup table: parameter -> Limits(lsl,tgt,usl)
-pub type LimitsTable = BTreeMap<String, Limits>;
+/// mark a parameter passing the limits checks, if the percentage of its values used is below the limit
+pub fn check_completeness(
+    yieldok: YieldOk,
+    numbers: &Numbers,
+    completeness_lim: f64,
+    mark: BitFlags<Mark>,
+) -> YieldOk {
+    match yieldok {
+        YieldOk::Yes | YieldOk::NoLimits
+            if mark.contains(Mark::Completeness) && numbers.completeness() < completeness_lim =>
+        {
+            YieldOk::Incomplete
+        }
+        _ => yieldok,
+    }
+}
+
+/// check range of values against the limits of a parameter
+pub fn check_numbers(
+    limits: Option<&Limits>,
+    numbers: &Numbers,
+    spec_yld_lim: f64,
+    ctrl_yld_lim: f64,
+    cpk_lim: f64,
+    mark: BitFlags<Mark>,
+) -> (YieldOk, Limits) {
+    let range = numbers.range();
+
+    let mut ok: (YieldOk, Limits);
+
+    let mut markit = mark.contains(Mark::SpecYield);
+
+    let lsl: &f64;
+    let usl: &f64;
+    let lcl: &f64;
+    let ucl: &f64;
+
+    let parlim;
+
+    match limits {
+        Some(lim) => {
+            parlim = lim.clone();
+            lsl = lim.get("lsl").unwrap();
+            usl = lim.get("usl").unwrap();
+            lcl = lim.get("lcl").unwrap();
+            ucl = lim.get("ucl").unwrap();
+        }
+        None => {
+            return (YieldOk::NoLimits, Limits::new());
+        }
+    }
+
+    if numbers.cnt().is_nan() || numbers.cnt() < 1.0 {
+        return (YieldOk::NoLimits, limits.unwrap().clone());
+    }
+
+    let spec_yld_lim = threshold(&parlim, "specyld_min", spec_yld_lim);
+    let ctrl_yld_lim = threshold(&parlim, "ctrlyld_min", ctrl_yld_lim);
+    let cpk_lim = threshold(&parlim, "cpk_min", cpk_lim);
+
+    // check spec, if available
+    ok = match (lsl.is_nan(), usl.is_nan()) {
+        (false, false) => {
+            let mut check = *lsl <= range.0 && *usl >= range.1;
+            if spec_yld_lim < 100.0 {
+                if numbers.yld(lsl, usl) > spec_yld_lim {
+                    check = true
+                } else {
+                    check = false
+                }
+            }
+            match check {
+                true => (marker(YieldOk::Yes, markit), parlim.clone()),
+                false => (marker(YieldOk::SpecYieldNot, markit), parlim.clone()),
+            }
+        }
+        (false, true) => {
+            let mut check = *lsl <= range.1;
+            if spec_yld_lim < 100.0 {
+                if numbers.yld(lsl, usl) > spec_yld_lim {
+                    check = true
+                } else {
+                    check = false
+                }
+            }
+            match check {
+                true => (marker(YieldOk::Yes, markit), parlim.clone()),
+                false => (marker(YieldOk::SpecYieldNot, markit), parlim.clone()),
+            }
+        }
+        (true, false) => {
+            let mut check = *usl >= range.0;
+            if spec_yld_lim < 100.0 {
+                if numbers.yld(lsl, usl) > spec_yld_lim {
+                    check = true
+                } else {
+                    check = false
+                }
+            }
+            match check {
+                true => (marker(YieldOk::Yes, markit), parlim.clone()),
+                false => (marker(YieldOk::SpecYieldNot, markit), parlim.clone()),
+            }
+        }
+        (true, true) => (YieldOk::NoLimits, parlim.clone()),
+    };
+
+    // check ctrl, if available
+    markit = mark.contains(Mark::ControlYield);
+    if ok.0 == YieldOk::NoLimits || ok.0 == YieldOk::Yes {
+        ok = match (lcl.is_nan(), ucl.is_nan()) {
+            (false, false) => {
+                let mut check = *lcl <= range.0 && *ucl >= range.1;
+                if ctrl_yld_lim < 100.0 {
+                    if numbers.yld(lcl, ucl) > ctrl_yld_lim {
+                        check = true
+                    } else {
+                        check = false
+                    }
+                }
+                match check {
+                    true => (marker(YieldOk::Yes, markit), parlim.clone()),
+                    false => (marker(YieldOk::CtrlYieldNot, markit), parlim.clone()),
+                }
+            }
+            (false, true) => {
+                let mut check = *lcl <= range.1;
+                if ctrl_yld_lim < 100.0 {
+                    if numbers.yld(lcl, ucl) > ctrl_yld_lim {
+                        check = true
+                    } else {
+                        check = false
+                    }
+                }
+                match check {
+                    true => (marker(YieldOk::Yes, markit), parlim.clone()),
+                    false => (marker(YieldOk::CtrlYieldNot, markit), parlim.clone()),
+                }
+            }
+            (true, false) => {
+                let mut check = *ucl >= range.0;
+                if ctrl_yld_lim < 100.0 {
+                    if numbers.yld(lcl, ucl) > ctrl_yld_lim {
+                        check = true
+                    } else {
+                        check = false
+                    }
+                }
+                match check {
+                    true => (marker(YieldOk::Yes, markit), parlim.clone()),
+                    false => (marker(YieldOk::CtrlYieldNot, markit), parlim.clone()),
+                }
+            }
+            (true, true) => (YieldOk::NoLimits, parlim.clone()),
+        }
+    }
+
+    // check cpk, if available
+    markit = mark.contains(Mark::Cpk);
+    if ok.0 == YieldOk::NoLimits || ok.0 == YieldOk::Yes {
+        ok = if numbers.cpk(lsl, usl) < cpk_lim {
+            (marker(YieldOk::CpkNot, markit), parlim.clone())
+        } else {
+            if lcl.is_nan() && ucl.is_nan() {
+                (marker(YieldOk::NoLimits, markit), parlim.clone())
+            } else {
+                (marker(YieldOk::Yes, markit), parlim.clone())
+            }
+        };
+    }
+
+    ok
+}
+
+/// Limits with all limits and filters unset
+fn nan_limits() -> Limits {
+    let mut limits = Limits::new();
+    for key in [
+        "lsl",
+        "tgt",
+        "usl",
+        "lcl",
+        "ucl",
+        "flt_iqr",
+        "flt_zsc",
+        "flt_low",
+        "flt_upp",
+        "cpk_min",
+        "specyld_min",
+        "ctrlyld_min",
+    ]
+    .iter()
+    {
+        limits.insert(key.to_string(), f64::NAN);
+    }
+    limits
+}
+
+/// true, if PAR of a limits row is a glob like `VTH_SITE*` or a regex like `/^VTH_SITE[0-9]+$/`
+fn is_pattern(par: &str) -> bool {
+    is_regex(par) || par.contains(['*', '?', '['])
+}
+
+fn is_regex(par: &str) -> bool {
+    par.len() >= 2 && par.starts_with('/') && par.ends_with('/')
+}
+
+/// PAR compiled, if it is a glob or regex
+fn compile_pattern(par: &str) -> Result<Option<ParamPattern>, String> {
+    match is_pattern(par) {
+        true => ParamPattern::parse(par).map(Some),
+        false => Ok(None),
+    }
+}
+
+/// count of literal characters of a PAR pattern, the pattern with more is more specific
+fn specificity(pattern: &str) -> usize {
+    match is_regex(pattern) {
+        true => {
+            let mut escaped = false;
+            let mut count = 0;
+            for c in pattern[1..pattern.len() - 1].chars() {
+                if !escaped && (c.is_alphanumeric() || c == '_') {
+                    count += 1;
+                }
+                escaped = !escaped && c == '\\';
+            }
+            count
+        }
+        false => {
+            let mut in_class = false;
+            let mut count = 0;
+            for c in pattern.chars() {
+                match c {
+                    '[' => in_class = true,
+                    ']' if in_class => {
+                        in_class = false;
+                        count += 1;
+                    }
+                    '*' | '?' => (),
+                    _ if !in_class => count += 1,
+                    _ => (),
+                }
+            }
+            count
+        }
+    }
+}
+
+/// limits key (lsl, usl, .., flt_iqr, ..) -> name of the limits file it is set by
+pub type Provenance = BTreeMap<String, String>;
+
+/// set the limits given (not NaN) of a limits file over the ones of previous files,
+/// the filter is replaced as a whole
+fn merge_limits(limits: &mut Limits, source: &mut Provenance, new: Limits, file: &str) {
+    let is_filter = |key: &str| key.starts_with("flt_");
+    if new.iter().any(|(key, v)| is_filter(key) && !v.is_nan()) {
+        for (key, v) in limits.iter_mut().filter(|(key, _)| is_filter(key)) {
+            *v = f64::NAN;
+            source.remove(key);
+        }
+    }
+    for (key, v) in new.into_iter().filter(|(_, v)| !v.is_nan()) {
+        source.insert(key.clone(), file.to_string());
+        limits.insert(key, v);
+    }
+}
+
+/// the limits files of the limits, e.g. `lsl usl: global.lim.csv | ucl: local.lim.csv`
+pub fn provenance_caption(source: &Provenance) -> String {
+    let mut files: Vec<(&String, Vec<&str>)> = vec![];
+    for key in [
+        "lsl",
+        "tgt",
+        "usl",
+        "lcl",
+        "ucl",
+        "flt_iqr",
+        "flt_zsc",
+        "flt_low",
+        "flt_upp",
+        "cpk_min",
+        "specyld_min",
+        "ctrlyld_min",
+    ]
+    .iter()
+    {
+        let file = match source.get(*key) {
+            Some(file) => file,
+            None => continue,
+        };
+        let name = match key.starts_with("flt_") {
+            true => "filter",
+            false => key,
+        };
+        match files.iter_mut().find(|(f, _)| *f == file) {
+            Some((_, names)) if !names.contains(&name) => names.push(name),
+            Some(_) => (),
+            None => files.push((file, vec![name])),
+        }
+    }
+    files
+        .iter()
+        .map(|(file, names)| format!("{}: {}", names.join(" "), file))
+        .collect::<Vec<String>>()
+        .join(" | ")
+}
+
+/// evaluate the pending limits using the data or not, with the TGT of the merged row,
+/// keyed limits without TGT use that of the row without keys
+fn resolve_pending(
+    table: &mut LimitsTable,
+    uses_data: bool,
+    stat: &dyn Fn(Stat, &str) -> Option<f64>,
+) -> Result<(), String> {
+    for row in table.values_mut() {
+        let row_tgt = row.limits.get("tgt").copied().unwrap_or(f64::NAN);
+        let keyed = row
+            .keyed
+            .iter_mut()
+            .map(|k| (&mut k.limits, &mut k.pending));
+        for (limits, pending) in std::iter::once((&mut row.limits, &mut row.pending)).chain(keyed) {
+            let tgt = match limits.get("tgt") {
+                Some(tgt) if !tgt.is_nan() => *tgt,
+                _ => row_tgt,
+            };
+            let (now, later): (Vec<PendingLimit>, Vec<PendingLimit>) = pending
+                .drain(..)
+                .partition(|p| p.expr.uses_data() == uses_data);
+            *pending = later;
+            for p in now {
+                let x = p.expr.eval(&p.limit, tgt, stat).map_err(|e| {
+                    format!(
+                        "cannot resolve '{}' in column {} for {}: {}.",
+                        p.cell,
+                        p.limit.to_uppercase(),
+                        p.origin,
+                        e
+                    )
+                })?;
+                limits.insert(p.limit, x);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// limits alike in all limits, the others unset, e.g. of a parameter checked against limits per subset of the rows
+pub fn common_limits(all: &[Limits]) -> Limits {
+    let mut common = all.first().cloned().unwrap_or_default();
+    for limits in all.iter().skip(1) {
+        for (key, x) in common.iter_mut() {
+            if limits.get(key) != Some(x) {
+                *x = f64::NAN;
+            }
+        }
+    }
+    common
+}
+
+/// limits of a parameter for the rows with the key values, e.g. PRODUCT/TYPE=PT1,
+/// and/or with the timestamp within VALID_FROM and VALID_TO
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct KeyedLimits {
+    /// (column, value) pairs from the KEY columns
+    pub keys: Vec<(String, String)>,
+    /// validity, unbounded unless chosen by the timestamp of the rows
+    pub valid: TimeWindow,
+    pub limits: Limits,
+    pub revision: String,
+    /// limits file of each limit and filter set
+    pub source: Provenance,
+    /// limits with expressions using statistics of the data
+    pub pending: Vec<PendingLimit>,
+}
+
+impl KeyedLimits {
+    /// true, if all keys are among the (column, value) pairs and the time is valid
+    pub fn matches(&self, pairs: &[(String, String)], time: Option<&NaiveDateTime>) -> bool {
+        self.keys.iter().all(|key| pairs.contains(key))
+            && (self.valid.is_unbounded() || time.map(|t| self.valid.contains(t)).unwrap_or(false))
+    }
+}
+
+/// one parameter row of the limits file
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LimitsRow {
+    /// limits of the row without keys, used for rows not matching any keyed limits
+    pub limits: Limits,
+    /// unit of the parameter from the UNIT column, may be empty
+    pub unit: String,
+    /// revision of the limits from the REVISION column, may be empty
+    pub revision: String,
+    /// limits file of each limit and filter set
+    pub source: Provenance,
+    /// limits of rows with KEY columns set
+    pub keyed: Vec<KeyedLimits>,
+    /// limits with expressions using statistics of the data
+    pub pending: Vec<PendingLimit>,
+    /// PAR compiled, if it is a glob or regex
+    pub pattern: Option<ParamPattern>,
+    /// position of the row in the limits files read, the first of equally specific patterns is used
+    pub order: usize,
+}
+
+impl LimitsRow {
+    /// limits of the keyed limits i, those not set there are the limits of the row without keys,
+    /// with the limits file of each
+    pub fn keyed_limits(&self, i: usize) -> (Limits, Provenance) {
+        let keyed = &self.keyed[i];
+        let mut limits = keyed.limits.clone();
+        let mut source = keyed.source.clone();
+        for (key, x) in limits.iter_mut().filter(|(_, x)| x.is_nan()) {
+            if let Some(y) = self.limits.get(key) {
+                *x = *y;
+            }
+            if let Some(file) = self.source.get(key) {
+                source.entry(key.clone()).or_insert_with(|| file.clone());
+            }
+        }
+        (limits, source)
+    }
+
+    /// index of the matching keyed limits with the most keys, of these the latest valid
+    pub fn keyed_index(
+        &self,
+        pairs: &[(String, String)],
+        time: Option<&NaiveDateTime>,
+    ) -> Option<usize> {
+        let mut best: Option<usize> = None;
+        for (i, k) in self.keyed.iter().enumerate() {
+            if k.matches(pairs, time)
+                && best
+                    .map(|b| {
+                        (k.keys.len(), k.valid.from)
+                            > (self.keyed[b].keys.len(), self.keyed[b].valid.from)
+                    })
+                    .unwrap_or(true)
+            {
+                best = Some(i);
+            }
+        }
+        best
+    }
+}
+
+/// the worse of two results of limits checks, e.g. of two subsets of the values
+pub fn worst(a: YieldOk, b: YieldOk) -> YieldOk {
+    let rank = |y: YieldOk| match y {
+        YieldOk::SpecYieldNot => 5,
+        YieldOk::CtrlYieldNot => 4,
+        YieldOk::CpkNot => 3,
+        YieldOk::Incomplete => 2,
+        YieldOk::Yes => 1,
+        YieldOk::NoLimits => 0,
+    };
+    match rank(b) > rank(a) {
+        true => b,
+        false => a,
+    }
+}
+
+/// lookup table: parameter -> LimitsRow(Limits(lsl,tgt,usl), unit),
+/// the parameter may be a glob or /regex/ matching several parameters
+pub type LimitsTable = BTreeMap<String, LimitsRow>;
 
 /// f64 extensions trait
 pub trait LimitsTableExt<T> {
-    fn read_limits(&mut self, limpath: &String) -> Result<(), String>;
+    fn read_limits(&mut self, limpath: &str, qtableprops: &QTableProps) -> Result<(), String>;
+    fn add_data_limits(&mut self, datpath: &str, parsed: &ParsedFiles);
     fn check_limits(
         &self,
         par: &str,
@@ -67,23 +545,31 @@ pub trait LimitsTableExt<T> {
         marknot: BitFlags<Mark>,
     ) -> (YieldOk, Limits);
     fn get_filter(&self, par: &str, flt: &Filter) -> Filter;
+    fn get_row(&self, par: &str) -> Option<(&String, &LimitsRow)>;
+    fn get_limits(&self, par: &str) -> Option<&Limits>;
+    fn get_unit(&self, par: &str) -> String;
+    fn report_matches(&self, pars: &[(usize, String)]);
+    fn revisions(&self) -> Vec<String>;
+    fn has_pending(&self) -> bool;
+    fn resolve_targets(&mut self) -> Result<(), String>;
+    fn resolve(&mut self, stat: &dyn Fn(Stat, &str) -> Option<f64>) -> Result<(), String>;
 }
 
 impl LimitsTableExt<LimitsTable> for LimitsTable {
     /// add limits from limits file .csv to LimitsTable
-    fn read_limits(&mut self, limpath: &String) -> Result<(), String> {
+    fn read_limits(&mut self, limpath: &str, qtableprops: &QTableProps) -> Result<(), String> {
         if *limpath != "".to_string() {
-            let limfile = match File::open(limpath) {
+            let (mut rdr, dialect) = match open_reader(limpath, &qtableprops.dialect) {
                 Err(e) => {
                     return Err(format!(
                         "could not open CSV limitfile '{}': '{}'.",
                         limpath, e
                     ));
                 }
-                Ok(limfile) => limfile,
+                Ok(rdr) => rdr,
             };
-            let mut rdr: Reader<File> = Reader::from_reader(limfile);
 
+            let file = source_name(limpath);
             let mut limcolumns: HashMap<String, usize> = HashMap::new();
             limcolumns.insert("use".to_string(), 9999);
             limcolumns.insert("par".to_string(), 9999);
@@ -92,6 +578,13 @@ impl LimitsTableExt<LimitsTable> for LimitsTable {
             limcolumns.insert("usl".to_string(), 9999);
             limcolumns.insert("lcl".to_string(), 9999);
             limcolumns.insert("ucl".to_string(), 9999);
+            limcolumns.insert("unit".to_string(), 9999);
+            limcolumns.insert("valid_from".to_string(), 9999);
+            limcolumns.insert("valid_to".to_string(), 9999);
+            limcolumns.insert("revision".to_string(), 9999);
+            limcolumns.insert("cpk_min".to_string(), 9999);
+            limcolumns.insert("specyld_min".to_string(), 9999);
+            limcolumns.insert("ctrlyld_min".to_string(), 9999);
 
             limcolumns.insert("<fil".to_string(), 9999);
             limcolumns.insert("ter>".to_string(), 9999);
@@ -106,8 +599,14 @@ impl LimitsTableExt<LimitsTable> for LimitsTable {
                 }
             };
 
+            // columns KEY, KEY2, .. with COLUMN=VALUE of the data rows the limits are for
+            let mut keycolumns: Vec<usize> = vec![];
+
             for (i, h) in headers.iter().enumerate() {
                 let v = h.to_uppercase();
+                if v.starts_with("KEY") {
+                    keycolumns.push(i);
+                }
                 let w = &v;
                 let u: &str = &w;
                 match u {
@@ -118,6 +617,13 @@ impl LimitsTableExt<LimitsTable> for LimitsTable {
                     "USL" => limcolumns.insert("usl".to_string(), i),
                     "LCL" => limcolumns.insert("lcl".to_string(), i),
                     "UCL" => limcolumns.insert("ucl".to_string(), i),
+                    "UNIT" => limcolumns.insert("unit".to_string(), i),
+                    "VALID_FROM" => limcolumns.insert("valid_from".to_string(), i),
+                    "VALID_TO" => limcolumns.insert("valid_to".to_string(), i),
+                    "REVISION" => limcolumns.insert("revision".to_string(), i),
+                    "CPK_MIN" => limcolumns.insert("cpk_min".to_string(), i),
+                    "SPECYLD_MIN" => limcolumns.insert("specyld_min".to_string(), i),
+                    "CTRLYLD_MIN" => limcolumns.insert("ctrlyld_min".to_string(), i),
                     "<FIL" => limcolumns.insert("<fil".to_string(), i),
                     "TER>" => limcolumns.insert("ter>".to_string(), i),
                     _ => None,
@@ -134,23 +640,34 @@ impl LimitsTableExt<LimitsTable> for LimitsTable {
 
                 let mut use_it: bool = false;
                 let mut par: &str = Default::default();
-                let mut limits = Limits::new();
-                limits.insert("lsl".to_string(), NAN);
-                limits.insert("tgt".to_string(), NAN);
-                limits.insert("usl".to_string(), NAN);
-                limits.insert("lcl".to_string(), NAN);
-                limits.insert("ucl".to_string(), NAN);
-
-                limits.insert("flt_iqr".to_string(), NAN);
-                limits.insert("flt_zsc".to_string(), NAN);
-                limits.insert("flt_low".to_string(), NAN);
-                limits.insert("flt_upp".to_string(), NAN);
+                let mut limits = nan_limits();
+                let mut unit = String::new();
+                let mut valid_from = "";
+                let mut valid_to = "";
+                let mut revision = String::new();
+                // (limit, cell) of the limit cells not being a number
+                let mut expressions: Vec<(&str, &str)> = vec![];
 
                 let mut iqr_next = false;
                 let mut zsc_next = false;
                 for (i, v) in record.iter().enumerate() {
                     for lim in [
-                        "use", "par", "lsl", "tgt", "usl", "lcl", "ucl", "<fil", "ter>",
+                        "use",
+                        "par",
+                        "lsl",
+                        "tgt",
+                        "usl",
+                        "lcl",
+                        "ucl",
+                        "unit",
+                        "valid_from",
+                        "valid_to",
+                        "revision",
+                        "cpk_min",
+                        "specyld_min",
+                        "ctrlyld_min",
+                        "<fil",
+                        "ter>",
                     ]
                     .iter()
                     {
@@ -165,22 +682,20 @@ impl LimitsTableExt<LimitsTable> for LimitsTable {
                                     }
                                 }
                                 &"par" => par = v,
+                                &"unit" => unit = v.trim().to_string(),
+                                &"valid_from" => valid_from = v,
+                                &"valid_to" => valid_to = v,
+                                &"revision" => revision = v.trim().to_string(),
                                 &"<fil" => match v {
                                     "iqr" => iqr_next = true,
                                     "zscore" => zsc_next = true,
                                     _ => {
-                                        let f = match v.parse::<f64>() {
-                                            Ok(v) => v,
-                                            Err(_) => NAN,
-                                        };
+                                        let f = dialect.parse_f64(v).unwrap_or(f64::NAN);
                                         limits.insert("flt_low".to_string(), f);
                                     }
                                 },
                                 &"ter>" => {
-                                    let f = match v.parse::<f64>() {
-                                        Ok(v) => v,
-                                        Err(_) => NAN,
-                                    };
+                                    let f = dialect.parse_f64(v).unwrap_or(f64::NAN);
                                     if iqr_next {
                                         limits.insert("flt_iqr".to_string(), f);
                                     } else {
@@ -191,185 +706,232 @@ impl LimitsTableExt<LimitsTable> for LimitsTable {
                                         }
                                     }
                                 }
+                                &"lsl" | &"usl" | &"lcl" | &"ucl"
+                                    if !v.trim().is_empty() && dialect.parse_f64(v).is_none() =>
+                                {
+                                    expressions.push((lim, v));
+                                }
                                 _ => {
-                                    let f = match v.parse::<f64>() {
-                                        Ok(v) => v,
-                                        Err(_) => NAN,
-                                    };
+                                    let f = dialect.parse_f64(v).unwrap_or(f64::NAN);
                                     limits.insert(lim.to_string(), f);
                                 }
                             }
                         }
                     }
                 }
-                if use_it {
-                    &self.insert(par.to_string(), limits);
+                let mut keys: Vec<(String, String)> = vec![];
+                for i in keycolumns.iter() {
+                    let v = record.get(*i).unwrap_or("").trim();
+                    if v.is_empty() {
+                        continue;
+                    }
+                    match v.split_once('=') {
+                        Some((column, value)) => keys.push((column.trim().to_string(), value.trim().to_string())),
+                        None => {
+                            return Err(format!(
+                                "invalid key '{}' for PAR '{}' in limitfile '{}', expected COLUMN=VALUE.",
+                                v, par, limpath
+                            ))
+                        }
+                    }
                 }
-            }
-        }
-        Ok(())
-    }
-
-    /// check range of values against LimitsTable by parameter name
-    fn check_limits(
-        &self,
-        par: &str,
-        numbers: &Numbers,
-        spec_yld_lim: f64,
-        ctrl_yld_lim: f64,
-        cpk_lim: f64,
-        mark: BitFlags<Mark>,
-    ) -> (YieldOk, Limits) {
-        let range = numbers.range();
-        let limits = self.get(par);
-
-        let mut ok: (YieldOk, Limits);
-
-        let mut markit = mark.contains(Mark::SpecYield);
 
-        let lsl: &f64;
-        let usl: &f64;
-        let lcl: &f64;
-        let ucl: &f64;
-
-        let parlim;
-
-        match limits {
-            Some(lim) => {
-                parlim = lim.clone();
-                lsl = lim.get("lsl").unwrap();
-                usl = lim.get("usl").unwrap();
-                lcl = lim.get("lcl").unwrap();
-                ucl = lim.get("ucl").unwrap();
-            }
-            None => {
-                return (YieldOk::NoLimits, Limits::new());
-            }
-        }
-
-        if numbers.cnt().is_nan() || numbers.cnt() < 1.0 {
-            return (YieldOk::NoLimits, limits.unwrap().clone());
-        }
-
-        // check spec, if available
-        ok = match (lsl.is_nan(), usl.is_nan()) {
-            (false, false) => {
-                let mut check = *lsl <= range.0 && *usl >= range.1;
-                if spec_yld_lim < 100.0 {
-                    if numbers.yld(lsl, usl) > spec_yld_lim {
-                        check = true
-                    } else {
-                        check = false
+                let mut valid = TimeWindow::default();
+                // a VALID_TO without time includes that day
+                for (v, bound, is_to) in [
+                    (valid_from, &mut valid.from, false),
+                    (valid_to, &mut valid.to, true),
+                ] {
+                    if v.trim().is_empty() {
+                        continue;
                     }
-                }
-                match check {
-                    true => (marker(YieldOk::Yes, markit), parlim.clone()),
-                    false => (marker(YieldOk::SpecYieldNot, markit), parlim.clone()),
-                }
-            }
-            (false, true) => {
-                let mut check = *lsl <= range.1;
-                if spec_yld_lim < 100.0 {
-                    if numbers.yld(lsl, usl) > spec_yld_lim {
-                        check = true
-                    } else {
-                        check = false
+                    let parsed = match is_to {
+                        true => parse_time_to(v, ""),
+                        false => parse_time(v, ""),
+                    };
+                    match parsed {
+                        Some(t) => *bound = Some(t),
+                        None => {
+                            return Err(format!(
+                                "invalid date '{}' for PAR '{}' in limitfile '{}'.",
+                                v, par, limpath
+                            ))
+                        }
                     }
                 }
-                match check {
-                    true => (marker(YieldOk::Yes, markit), parlim.clone()),
-                    false => (marker(YieldOk::SpecYieldNot, markit), parlim.clone()),
-                }
-            }
-            (true, false) => {
-                let mut check = *usl >= range.0;
-                if spec_yld_lim < 100.0 {
-                    if numbers.yld(lsl, usl) > spec_yld_lim {
-                        check = true
-                    } else {
-                        check = false
+                // limits not valid at the date are skipped, with the date per row they are kept keyed
+                if let Some(date) = qtableprops.limits_date.date() {
+                    if !valid.contains(&date) {
+                        continue;
                     }
+                    valid = TimeWindow::default();
                 }
-                match check {
-                    true => (marker(YieldOk::Yes, markit), parlim.clone()),
-                    false => (marker(YieldOk::SpecYieldNot, markit), parlim.clone()),
-                }
-            }
-            (true, true) => (YieldOk::NoLimits, parlim.clone()),
-        };
 
-        // check ctrl, if available
-        markit = mark.contains(Mark::ControlYield);
-        if ok.0 == YieldOk::NoLimits || ok.0 == YieldOk::Yes {
-            ok = match (lcl.is_nan(), ucl.is_nan()) {
-                (false, false) => {
-                    let mut check = *lcl <= range.0 && *ucl >= range.1;
-                    if ctrl_yld_lim < 100.0 {
-                        if numbers.yld(lcl, ucl) > ctrl_yld_lim {
-                            check = true
-                        } else {
-                            check = false
+                // expressions are resolved with the TGT of the merged rows, those using the data when it is read
+                let mut pending: Vec<PendingLimit> = vec![];
+                if use_it {
+                    let origin = format!("PAR '{}' in limitfile '{}'", par, limpath);
+                    for (lim, cell) in expressions {
+                        let invalid = |e: String| {
+                            format!(
+                                "invalid expression '{}' in column {} for {}: {}.",
+                                cell,
+                                lim.to_uppercase(),
+                                origin,
+                                e
+                            )
+                        };
+                        let expr = Expr::parse(cell, &dialect).map_err(invalid)?;
+                        if expr.uses_data() && !(keys.is_empty() && valid.is_unbounded()) {
+                            return Err(invalid("statistics of the data like mean(PAR) are of all rows, they can not be used in a row with KEY or VALID_FROM/VALID_TO columns".to_string()));
                         }
-                    }
-                    match check {
-                        true => (marker(YieldOk::Yes, markit), parlim.clone()),
-                        false => (marker(YieldOk::CtrlYieldNot, markit), parlim.clone()),
+                        pending.push(PendingLimit {
+                            limit: lim.to_string(),
+                            expr,
+                            cell: cell.to_string(),
+                            origin: origin.clone(),
+                        });
                     }
                 }
-                (false, true) => {
-                    let mut check = *lcl <= range.1;
-                    if ctrl_yld_lim < 100.0 {
-                        if numbers.yld(lcl, ucl) > ctrl_yld_lim {
-                            check = true
-                        } else {
-                            check = false
-                        }
+
+                // fields set override those of previous rows and limits files
+                if use_it {
+                    if !self.contains_key(par) {
+                        let pattern = compile_pattern(par)
+                            .map_err(|e| format!("limits file '{}': {}", limpath, e))?;
+                        let order = self.len();
+                        self.insert(
+                            par.to_string(),
+                            LimitsRow {
+                                limits: nan_limits(),
+                                pattern,
+                                order,
+                                ..Default::default()
+                            },
+                        );
                     }
-                    match check {
-                        true => (marker(YieldOk::Yes, markit), parlim.clone()),
-                        false => (marker(YieldOk::CtrlYieldNot, markit), parlim.clone()),
+                    let row = self.get_mut(par).unwrap();
+                    if !unit.is_empty() {
+                        row.unit = unit;
                     }
-                }
-                (true, false) => {
-                    let mut check = *ucl >= range.0;
-                    if ctrl_yld_lim < 100.0 {
-                        if numbers.yld(lcl, ucl) > ctrl_yld_lim {
-                            check = true
-                        } else {
-                            check = false
-                        }
+                    let (row_limits, source, row_revision, row_pending) =
+                        match keys.is_empty() && valid.is_unbounded() {
+                            true => (
+                                &mut row.limits,
+                                &mut row.source,
+                                &mut row.revision,
+                                &mut row.pending,
+                            ),
+                            false => {
+                                let i = match row
+                                    .keyed
+                                    .iter()
+                                    .position(|k| k.keys == keys && k.valid == valid)
+                                {
+                                    Some(i) => i,
+                                    None => {
+                                        row.keyed.push(KeyedLimits {
+                                            keys,
+                                            valid,
+                                            limits: nan_limits(),
+                                            ..Default::default()
+                                        });
+                                        row.keyed.len() - 1
+                                    }
+                                };
+                                let keyed = &mut row.keyed[i];
+                                (
+                                    &mut keyed.limits,
+                                    &mut keyed.source,
+                                    &mut keyed.revision,
+                                    &mut keyed.pending,
+                                )
+                            }
+                        };
+                    row_pending.retain(|p| limits[&p.limit].is_nan());
+                    merge_limits(row_limits, source, limits, &file);
+                    for p in pending {
+                        row_pending.retain(|q| q.limit != p.limit);
+                        source.insert(p.limit.clone(), file.clone());
+                        row_pending.push(p);
                     }
-                    match check {
-                        true => (marker(YieldOk::Yes, markit), parlim.clone()),
-                        false => (marker(YieldOk::CtrlYieldNot, markit), parlim.clone()),
+                    if !revision.is_empty() {
+                        *row_revision = revision;
                     }
                 }
-                (true, true) => (YieldOk::NoLimits, parlim.clone()),
             }
         }
+        Ok(())
+    }
 
-        // check cpk, if available
-        markit = mark.contains(Mark::Cpk);
-        if ok.0 == YieldOk::NoLimits || ok.0 == YieldOk::Yes {
-            ok = if numbers.cpk(lsl, usl) < cpk_lim {
-                (marker(YieldOk::CpkNot, markit), parlim.clone())
-            } else {
-                if lcl.is_nan() && ucl.is_nan() {
-                    (marker(YieldOk::NoLimits, markit), parlim.clone())
-                } else {
-                    (marker(YieldOk::Yes, markit), parlim.clone())
-                }
+    /// add the limits carried by a STDF or Q-DAS data file
+    /// for the LSL, TGT and USL not set by the limits files
+    fn add_data_limits(&mut self, datpath: &str, parsed: &ParsedFiles) {
+        // (parameter, lsl, tgt, usl)
+        let parlimits: Vec<(&String, f64, f64, f64)> = match parsed.get(datpath) {
+            Some(ParsedFile::Stdf(stdf)) => stdf
+                .tests
+                .iter()
+                .map(|t| (&t.name, t.lsl, f64::NAN, t.usl))
+                .collect(),
+            Some(ParsedFile::Qdas(qdas)) => qdas
+                .chars
+                .iter()
+                .map(|c| (&c.name, c.lsl, c.tgt, c.usl))
+                .collect(),
+            None => return,
+        };
+
+        // the limits of the file fill the limits not set by the limits files, a row matched by
+        // pattern is copied for the parameter, so its other fields are kept
+        for (par, lsl, tgt, usl) in parlimits {
+            if lsl.is_nan() && tgt.is_nan() && usl.is_nan() {
+                continue;
+            }
+            let mut row = match self.get_row(par) {
+                Some((_, row)) => row.clone(),
+                None => LimitsRow {
+                    limits: nan_limits(),
+                    ..Default::default()
+                },
             };
+            row.pattern = None;
+            for (key, x) in [("lsl", lsl), ("tgt", tgt), ("usl", usl)] {
+                if x.is_nan() || !row.limits.get(key).copied().unwrap_or(f64::NAN).is_nan() {
+                    continue;
+                }
+                row.limits.insert(key.to_string(), x);
+                row.source.insert(key.to_string(), source_name(datpath));
+            }
+            self.insert(par.clone(), row);
         }
+    }
 
-        ok
+    /// check range of values against LimitsTable by parameter name
+    fn check_limits(
+        &self,
+        par: &str,
+        numbers: &Numbers,
+        spec_yld_lim: f64,
+        ctrl_yld_lim: f64,
+        cpk_lim: f64,
+        mark: BitFlags<Mark>,
+    ) -> (YieldOk, Limits) {
+        check_numbers(
+            self.get_limits(par),
+            numbers,
+            spec_yld_lim,
+            ctrl_yld_lim,
+            cpk_lim,
+            mark,
+        )
     }
 
     fn get_filter(&self, par: &str, flt: &Filter) -> Filter {
         if self.len() < 1 {
             return flt.clone();
         }
-        match self.get(par) {
+        match self.get_limits(par) {
             None => return flt.clone(),
             Some(p) => {
                 let iqr = p.get("flt_iqr").unwrap();
@@ -395,4 +957,303 @@ impl LimitsTableExt<LimitsTable> for LimitsTable {
         }
         flt.clone()
     }
+
+    /// limits row of the parameter: the exact PAR, else the most specific matching pattern,
+    /// of equally specific patterns the first in the limits files
+    fn get_row(&self, par: &str) -> Option<(&String, &LimitsRow)> {
+        if let Some(row) = self.get_key_value(par) {
+            return Some(row);
+        }
+        let mut found: Option<(&String, &LimitsRow, usize)> = None;
+        for (name, row) in self.iter() {
+            let pattern = match &row.pattern {
+                Some(pattern) if pattern.matches(par, 0) => pattern,
+                _ => continue,
+            };
+            let more_specific = match found {
+                None => true,
+                Some((_, f, s)) => {
+                    let sp = specificity(&pattern.pattern);
+                    sp > s || (sp == s && row.order < f.order)
+                }
+            };
+            if more_specific {
+                found = Some((name, row, specificity(&pattern.pattern)));
+            }
+        }
+        found.map(|(name, row, _)| (name, row))
+    }
+
+    fn get_limits(&self, par: &str) -> Option<&Limits> {
+        self.get_row(par).map(|(_, row)| &row.limits)
+    }
+
+    /// unit of the parameter from the limits file, empty if not set
+    fn get_unit(&self, par: &str) -> String {
+        match self.get_row(par) {
+            Some((_, row)) => row.unit.clone(),
+            None => "".to_string(),
+        }
+    }
+
+    /// distinct revisions of all limits rows read
+    fn revisions(&self) -> Vec<String> {
+        let mut revisions: Vec<String> = vec![];
+        for row in self.values() {
+            for revision in
+                std::iter::once(&row.revision).chain(row.keyed.iter().map(|k| &k.revision))
+            {
+                if !revision.is_empty() && !revisions.contains(revision) {
+                    revisions.push(revision.clone());
+                }
+            }
+        }
+        revisions
+    }
+
+    /// true, if limits with expressions using statistics of the data are to be resolved
+    fn has_pending(&self) -> bool {
+        self.values()
+            .any(|row| !row.pending.is_empty() || row.keyed.iter().any(|k| !k.pending.is_empty()))
+    }
+
+    /// evaluate the limits with expressions relative to TGT, after all limits files are read
+    fn resolve_targets(&mut self) -> Result<(), String> {
+        resolve_pending(self, false, &|_, _| None)
+    }
+
+    /// evaluate the limits with expressions using statistics of the data
+    fn resolve(&mut self, stat: &dyn Fn(Stat, &str) -> Option<f64>) -> Result<(), String> {
+        resolve_pending(self, true, stat)
+    }
+
+    /// print the limits row used by each parameter and the pattern rows matching none
+    fn report_matches(&self, pars: &[(usize, String)]) {
+        let mut used: Vec<&String> = vec![];
+        println!("limits rows matched:");
+        for (_, name) in pars.iter() {
+            match self.get_row(name) {
+                Some((p, row)) if p == name => {
+                    println!("  {}: exact ({})", name, provenance_caption(&row.source));
+                    used.push(p);
+                }
+                Some((p, row)) => {
+                    println!(
+                        "  {}: pattern '{}' ({})",
+                        name,
+                        p,
+                        provenance_caption(&row.source)
+                    );
+                    used.push(p);
+                }
+                None => println!("  {}: no limits", name),
+            }
+        }
+        for pattern in self.keys().filter(|p| is_pattern(p) && !used.contains(p)) {
+            println!("limits row '{}' matches no parameter.", pattern);
+        }
+    }
+}
+
+/// check a limits file, returns its issues with row and column.
+/// Only rows with USE checked are checked, `names` are the parameters of the data.
+pub fn lint_limits(
+    limpath: &str,
+    names: &[String],
+    qtableprops: &QTableProps,
+) -> Result<Vec<String>, String> {
+    let (mut rdr, dialect) = match open_reader(limpath, &qtableprops.dialect) {
+        Err(e) => {
+            return Err(format!(
+                "could not open CSV limitfile '{}': '{}'.",
+                limpath, e
+            ))
+        }
+        Ok(rdr) => rdr,
+    };
+    let headers: Vec<String> = match rdr.headers() {
+        Ok(record) => record.iter().map(|h| h.trim().to_string()).collect(),
+        Err(e) => {
+            return Err(format!(
+                "could not read CSV limitfile '{}', '{}'.",
+                limpath, e
+            ))
+        }
+    };
+    let column = |name: &str| headers.iter().position(|h| h.to_uppercase() == name);
+    let keycolumns: Vec<usize> = (0..headers.len())
+        .filter(|i| headers[*i].to_uppercase().starts_with("KEY"))
+        .collect();
+
+    let mut issues: Vec<String> = vec![];
+    let (use_col, par_col) = match (column("USE"), column("PAR")) {
+        (Some(u), Some(p)) => (u, p),
+        _ => {
+            issues.push("header: columns USE and PAR are needed.".to_string());
+            return Ok(issues);
+        }
+    };
+
+    // (PAR, keys and validity) -> row of the first occurrence
+    let mut seen: HashMap<String, u64> = HashMap::new();
+    let mut unmatched: Vec<String> = vec![];
+    for result in rdr.records() {
+        let record = match result {
+            Err(e) => {
+                issues.push(format!("{}", e));
+                continue;
+            }
+            Ok(record) => record,
+        };
+        let row = record.position().map(|p| p.line()).unwrap_or(0);
+        let cell = |i: Option<usize>| i.and_then(|i| record.get(i)).unwrap_or("").trim();
+        if cell(Some(use_col)).is_empty() {
+            continue;
+        }
+        let mut issue = |col: &str, text: String| match col.is_empty() {
+            true => issues.push(format!("row {}: {}", row, text)),
+            false => issues.push(format!("row {}, column {}: {}", row, col, text)),
+        };
+
+        let par = cell(Some(par_col));
+        if par.is_empty() {
+            issue("PAR", "PAR is empty.".to_string());
+            continue;
+        }
+
+        // numbers of the limits, NaN if empty or an expression using the data
+        let tgt = dialect.parse_f64(cell(column("TGT"))).unwrap_or(f64::NAN);
+        let mut value = |name: &str| -> f64 {
+            let v = cell(column(name));
+            if v.is_empty() {
+                return f64::NAN;
+            }
+            if let Some(f) = dialect.parse_f64(v) {
+                return f;
+            }
+            if !["LSL", "USL", "LCL", "UCL"].contains(&name) {
+                issue(name, format!("'{}' is not a number.", v));
+                return f64::NAN;
+            }
+            let limit = name.to_lowercase();
+            match Expr::parse(v, &dialect) {
+                // the TGT may be set by another limits file
+                Ok(expr) if expr.uses_data() || tgt.is_nan() => f64::NAN,
+                Ok(expr) => expr.eval(&limit, tgt, &|_, _| None).unwrap_or_else(|e| {
+                    issue(name, format!("'{}' cannot be resolved: {}.", v, e));
+                    f64::NAN
+                }),
+                Err(e) => {
+                    issue(
+                        name,
+                        format!("'{}' is not a number or expression: {}.", v, e),
+                    );
+                    f64::NAN
+                }
+            }
+        };
+        let lsl = value("LSL");
+        let tgt = value("TGT");
+        let usl = value("USL");
+        let lcl = value("LCL");
+        let ucl = value("UCL");
+        let cpk_min = value("CPK_MIN");
+        let yld_mins = [
+            ("SPECYLD_MIN", value("SPECYLD_MIN")),
+            ("CTRLYLD_MIN", value("CTRLYLD_MIN")),
+        ];
+        for (a, an, b, bn) in [
+            (lsl, "LSL", tgt, "TGT"),
+            (tgt, "TGT", usl, "USL"),
+            (lsl, "LSL", usl, "USL"),
+            (lcl, "LCL", ucl, "UCL"),
+            (lsl, "LSL", lcl, "LCL"),
+            (ucl, "UCL", usl, "USL"),
+        ] {
+            if a > b {
+                issue(an, format!("{} {} is greater than {} {}.", an, a, bn, b));
+            }
+        }
+
+        if cpk_min < 0.0 {
+            issue("CPK_MIN", "CPK_MIN is negative.".to_string());
+        }
+        for (name, v) in yld_mins {
+            if !(0.0..=100.0).contains(&v) && !v.is_nan() {
+                issue(
+                    name,
+                    format!("{} {} is not a percentage from 0 to 100.", name, v),
+                );
+            }
+        }
+
+        let fil = cell(column("<FIL"));
+        let ter = cell(column("TER>"));
+        let keyword = matches!(fil, "iqr" | "zscore");
+        if !fil.is_empty() && !keyword && dialect.parse_f64(fil).is_none() {
+            issue(
+                "<FIL",
+                format!(
+                    "unknown filter '{}', allowed: iqr, zscore or a number.",
+                    fil
+                ),
+            );
+        }
+        if !ter.is_empty() && dialect.parse_f64(ter).is_none() {
+            issue("TER>", format!("'{}' is not a number.", ter));
+        }
+        if keyword && ter.is_empty() {
+            issue("TER>", format!("filter '{}' needs a factor.", fil));
+        }
+
+        let mut key = par.to_string();
+        for i in keycolumns.iter() {
+            let v = cell(Some(*i));
+            if !v.is_empty() && !v.contains('=') {
+                issue(&headers[*i], format!("key '{}' is not COLUMN=VALUE.", v));
+            }
+            key.push_str(&format!("|{}", v));
+        }
+        for name in ["VALID_FROM", "VALID_TO"] {
+            let v = cell(column(name));
+            if !v.is_empty() && parse_time(v, "").is_none() {
+                issue(name, format!("'{}' is not a date.", v));
+            }
+            key.push_str(&format!("|{}", v));
+        }
+        match seen.get(&key) {
+            Some(first) => issue(
+                "PAR",
+                format!(
+                    "'{}' duplicates row {}, its limits override those set there.",
+                    par, first
+                ),
+            ),
+            None => {
+                seen.insert(key, row);
+            }
+        }
+
+        // an exact name wins over the pattern, as in get_row, e.g. IDD[mA]
+        let matched = match compile_pattern(par) {
+            Ok(pattern) => {
+                names.iter().any(|n| n == par)
+                    || pattern
+                        .map(|pattern| names.iter().any(|n| pattern.matches(n, 0)))
+                        .unwrap_or(false)
+            }
+            Err(e) => {
+                issue("PAR", e);
+                continue;
+            }
+        };
+        if !matched && !unmatched.contains(&par.to_string()) {
+            issue(
+                "PAR",
+                format!("'{}' matches no parameter of the data.", par),
+            );
+            unmatched.push(par.to_string());
+        }
+    }
+    Ok(issues)
 }
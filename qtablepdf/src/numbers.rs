@@ -3,7 +3,6 @@
 use crate::qtable::Filter;
 use num::Float;
 use std::cmp::Ordering;
-use std::f64::NAN;
 
 /// f64 extensions trait
 pub trait F64Ext<T> {
@@ -76,24 +75,195 @@ fn value_nans_last<T: Float>(a: &T, b: &T) -> Ordering {
     }
 }
 
+/// error code values (low == high) or range of values of failed measurements, e.g. 9.99e37 = open
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sentinel {
+    pub low: f64,
+    pub high: f64,
+    pub label: String,
+}
+
+impl Sentinel {
+    /// true, if x is the code value or within the code range
+    pub fn matches(&self, x: f64) -> bool {
+        match self.low == self.high {
+            true => x == self.low || (x - self.low).abs() <= self.low.abs() * 1e-9,
+            false => x >= self.low && x <= self.high,
+        }
+    }
+}
+
+/// zero counts of the distinct labels of the sentinels, in config order
+pub fn code_counts(sentinels: &[Sentinel]) -> Vec<(String, f64)> {
+    let mut codes: Vec<(String, f64)> = vec![];
+    for s in sentinels.iter() {
+        if !codes.iter().any(|(label, _)| *label == s.label) {
+            codes.push((s.label.clone(), 0.0));
+        }
+    }
+    codes
+}
+
+/// index in the code counts of the first sentinel matching x
+pub fn code_index(x: f64, sentinels: &[Sentinel], codes: &[(String, f64)]) -> Option<usize> {
+    let s = sentinels.iter().find(|s| s.matches(x))?;
+    codes.iter().position(|(label, _)| *label == s.label)
+}
+
+/// exact statistics of a stream of values, whose distribution is only sketched
+#[derive(Debug, Clone, PartialEq)]
+pub struct Moments {
+    pub cnt: f64,
+    pub mean: f64,
+    /// sum of squared differences from the mean
+    pub m2: f64,
+    pub min: f64,
+    pub max: f64,
+    /// yields counted for limit pairs: (lower limit, upper limit, yield)
+    pub ylds: Vec<(f64, f64, f64)>,
+}
+
+/// counts of the values of a column not used for the statistics, by reason
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Quality {
+    /// all values of the column, used or not
+    pub rows: f64,
+    /// empty cells
+    pub missing: f64,
+    /// cells not parseable as number
+    pub unparseable: f64,
+    /// NaN values
+    pub nan: f64,
+    /// values at or above floatlimit
+    pub cutoff: f64,
+    /// outliers removed by the filter
+    pub filtered: f64,
+}
+
+/// sample quantile definitions of Hyndman and Fan (1996), as type 1 to 9 of R quantile()
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub enum Quantile {
+    /// inverse of the empirical distribution function
+    Type1,
+    /// like type 1, averaged at discontinuities
+    Type2,
+    /// nearest even order statistic (SAS)
+    Type3,
+    Type4,
+    Type5,
+    /// p(n+1), Minitab and Excel PERCENTILE.EXC
+    Type6,
+    /// 1+p(n-1), R, Excel PERCENTILE.INC and numpy
+    #[default]
+    Type7,
+    /// median unbiased
+    Type8,
+    /// unbiased for normal distributions
+    Type9,
+}
+
+impl Quantile {
+    /// quantile type from its number 1 to 9
+    pub fn from_number(n: usize) -> Option<Quantile> {
+        [
+            Quantile::Type1,
+            Quantile::Type2,
+            Quantile::Type3,
+            Quantile::Type4,
+            Quantile::Type5,
+            Quantile::Type6,
+            Quantile::Type7,
+            Quantile::Type8,
+            Quantile::Type9,
+        ]
+        .get(n.wrapping_sub(1))
+        .copied()
+    }
+
+    /// quantile p (0 to 1) of values sorted ascending
+    pub fn of(&self, sorted: &[f64], p: f64) -> f64 {
+        let n = sorted.len() as f64;
+        if sorted.is_empty() || !(0.0..=1.0).contains(&p) {
+            return f64::NAN;
+        }
+        let m = match self {
+            Quantile::Type1 | Quantile::Type2 | Quantile::Type4 => 0.0,
+            Quantile::Type3 => -0.5,
+            Quantile::Type5 => 0.5,
+            Quantile::Type6 => p,
+            Quantile::Type7 => 1.0 - p,
+            Quantile::Type8 => (p + 1.0) / 3.0,
+            Quantile::Type9 => p / 4.0 + 3.0 / 8.0,
+        };
+        // position j + g (1-based), with a fuzz against rounding errors like R
+        let h = n * p + m;
+        let fuzz = 4.0 * f64::EPSILON * h.abs().max(1.0);
+        let j = (h + fuzz).floor();
+        let g = match (h - j).abs() < fuzz {
+            true => 0.0,
+            false => h - j,
+        };
+        let gamma = match self {
+            Quantile::Type1 | Quantile::Type2 if g > 0.0 => 1.0,
+            Quantile::Type1 => 0.0,
+            Quantile::Type2 => 0.5,
+            Quantile::Type3 if g == 0.0 && j % 2.0 == 0.0 => 0.0,
+            Quantile::Type3 => 1.0,
+            _ => g,
+        };
+        let x = |i: f64| sorted[i.max(1.0).min(n) as usize - 1];
+        match gamma == 0.0 {
+            true => x(j),
+            false => (1.0 - gamma) * x(j) + gamma * x(j + 1.0),
+        }
+    }
+}
+
 /// vector of f64
 #[derive(Debug, Clone, PartialEq)]
 pub struct Numbers {
     pub(crate) data: Vec<f64>,
+    /// exact statistics, if `data` is only a sample of the distribution (streaming)
+    pub(crate) moments: Option<Moments>,
+    /// count of values per error code label
+    pub(crate) codes: Vec<(String, f64)>,
+    /// counts of the values dropped before the statistics
+    pub(crate) quality: Quality,
+    /// definition of median, percentiles and iqr
+    pub(crate) quantile: Quantile,
 }
 
 impl Numbers {
-    /// create new Numbers vector of f64 from vector of String,
-    /// skipping invalid values, float_limits, outliers
-    pub fn new(vals: &Vec<String>, float_limit: f64, filter_by: &Filter) -> Self {
-        let data = vals
-            .iter()
-            .filter_map(|s| s.parse::<f64>().ok())
-            .filter(|v| !v.is_nan())
-            .filter(|v| *v < float_limit)
-            .collect::<Vec<f64>>();
-        let numbers = Numbers::from_f64(data);
-        match filter_by {
+    /// create new Numbers vector of f64 from the parsed values of a column,
+    /// counting error codes, skipping invalid values, float_limits, outliers
+    pub fn new(
+        nums: &[f64],
+        float_limit: f64,
+        sentinels: &[Sentinel],
+        filter_by: &Filter,
+        quantile: Quantile,
+    ) -> Self {
+        let mut codes = code_counts(sentinels);
+        let mut quality = Quality {
+            rows: nums.len() as f64,
+            ..Default::default()
+        };
+        let mut data = Vec::with_capacity(nums.len());
+        for v in nums.iter().copied() {
+            if v.is_nan() {
+                quality.nan += 1.0;
+            } else if let Some(i) = code_index(v, sentinels, &codes) {
+                codes[i].1 += 1.0;
+            } else if v >= float_limit {
+                quality.cutoff += 1.0;
+            } else {
+                data.push(v);
+            }
+        }
+        let mut numbers = Numbers::from_f64(data);
+        numbers.quantile = quantile;
+        let before_filter = numbers.data.len();
+        let mut filtered = match filter_by {
             Filter::None => numbers,
             Filter::IQR(k) => {
                 let kiqr = *k * numbers.iqr();
@@ -145,16 +315,50 @@ impl Numbers {
                     .collect();
                 Numbers::from_f64(data)
             }
-        }
+        };
+        quality.filtered = (before_filter - filtered.data.len()) as f64;
+        filtered.codes = codes;
+        filtered.quality = quality;
+        filtered.quantile = quantile;
+        filtered
+    }
+
+    /// split the NaN count into the empty and unparseable cells of the column,
+    /// which are parsed as NaN
+    pub fn set_invalid(&mut self, missing: f64, unparseable: f64) {
+        self.quality.missing = missing;
+        self.quality.unparseable = unparseable;
+        self.quality.nan = (self.quality.nan - missing - unparseable).max(0.0);
     }
 
     pub fn from_f64(mut data: Vec<f64>) -> Self {
         data.sort_by(value_nans_last);
-        Numbers { data }
+        Numbers {
+            data,
+            moments: None,
+            codes: vec![],
+            quality: Quality::default(),
+            quantile: Quantile::default(),
+        }
+    }
+
+    /// create Numbers from exact moments and a sample representing the distribution
+    pub fn from_moments(sample: Vec<f64>, moments: Moments) -> Self {
+        let mut numbers = Numbers::from_f64(sample);
+        numbers.moments = Some(moments);
+        numbers
+    }
+
+    /// true, if quantiles, histogram and boxplot are approximated from a sketch
+    pub fn is_approximate(&self) -> bool {
+        self.moments.is_some()
     }
 
     /// mean of Numbers vector of f64
     pub fn mea(&self) -> f64 {
+        if let Some(m) = &self.moments {
+            return m.mean;
+        }
         let mut i = 0.0;
         let mut mean = 0.0;
         for x in &self.data {
@@ -166,18 +370,27 @@ impl Numbers {
         if i > 0.0 {
             mean
         } else {
-            NAN
+            f64::NAN
         }
     }
 
     /// yield of Numbers vector of f64
     pub fn yld(&self, lowlim: &f64, upplim: &f64) -> f64 {
         if lowlim.is_nan() && upplim.is_nan() {
-            return NAN;
+            return f64::NAN;
+        }
+        if let Some(m) = &self.moments {
+            let counted = m.ylds.iter().find(|(lo, hi, _)| {
+                (lo == lowlim || lo.is_nan() && lowlim.is_nan())
+                    && (hi == upplim || hi.is_nan() && upplim.is_nan())
+            });
+            if let Some((_, _, yld)) = counted {
+                return *yld;
+            }
         }
-        let cnt = self.cnt() as usize;
+        let cnt = self.data.len();
         if cnt == 0 {
-            return NAN;
+            return f64::NAN;
         }
         let mut lo = 0;
         let mut hi = 0;
@@ -192,7 +405,7 @@ impl Numbers {
 
     // k
     pub fn k(&self, lsl: &f64, tgt: &f64, usl: &f64) -> f64 {
-        let mut k = NAN;
+        let mut k = f64::NAN;
         let mea = &self.mea();
         if !tgt.is_nan() {
             if !lsl.is_nan() && !usl.is_nan() {
@@ -214,7 +427,7 @@ impl Numbers {
 
     // cpk
     pub fn cpk(&self, lsl: &f64, usl: &f64) -> f64 {
-        let mut cpk = NAN;
+        let mut cpk = f64::NAN;
         let mea = &self.mea();
         let std = &self.std();
         if !lsl.is_nan() && !usl.is_nan() {
@@ -232,7 +445,7 @@ impl Numbers {
 
     // cp
     pub fn cp(&self, lsl: &f64, usl: &f64) -> f64 {
-        let mut cp = NAN;
+        let mut cp = f64::NAN;
         let mea = &self.mea();
         let std = &self.std();
 
@@ -250,8 +463,14 @@ impl Numbers {
 
     /// variance of Numbers vector of f64
     pub fn var(&self) -> f64 {
+        if let Some(m) = &self.moments {
+            return match m.cnt > 1.0 {
+                true => m.m2 / (m.cnt - 1.0),
+                false => f64::NAN,
+            };
+        }
         let mut sum = match &self.data.iter().next() {
-            None => NAN,
+            None => f64::NAN,
             Some(x) => **x,
         };
         let mut i = 1.0;
@@ -268,7 +487,7 @@ impl Numbers {
         if i > 1.0 {
             variance / (i - 1.0)
         } else {
-            NAN
+            f64::NAN
         }
     }
     /// standard deviation of Numbers vector of f64
@@ -277,44 +496,49 @@ impl Numbers {
     }
     /// minimum of Numbers vector of f64
     pub fn min(&self) -> f64 {
+        if let Some(m) = &self.moments {
+            return m.min;
+        }
         match self.data.len() {
-            0 => NAN,
+            0 => f64::NAN,
             _ => self.data[0],
         }
     }
     /// maximum of Numbers vector of f64
     pub fn max(&self) -> f64 {
+        if let Some(m) = &self.moments {
+            return m.max;
+        }
         match self.data.len() {
-            0 => NAN,
+            0 => f64::NAN,
             _ => self.data[self.data.len() - 1],
         }
     }
     /// range(min,max) of Numbers vector of f64
     pub fn range(&self) -> (f64, f64) {
+        if let Some(m) = &self.moments {
+            return (m.min, m.max);
+        }
         match self.data.len() {
-            0 => (NAN, NAN),
+            0 => (f64::NAN, f64::NAN),
             _ => (self.data[0], self.data[self.data.len() - 1]),
         }
     }
-    /// percentile of Numbers vector of f64
+    /// percentile of Numbers vector of f64, as defined by its quantile type,
+    /// proc is 0 to 1 (1 is the maximum) or in percent above 1
     pub fn prc(&self, proc: f64) -> f64 {
         let mut p = proc.abs();
 
-        if p >= 1.0 {
-            p = p / 100.0;
+        if p > 1.0 {
+            p /= 100.0;
         }
-        if p >= 1.0 {
-            return NAN;
+        if p > 1.0 {
+            return f64::NAN;
         }
 
         match self.data.len() {
-            0 => NAN,
-            1 => self.data[0],
-            2 => self.mea(),
-            _ => {
-                let i = (p * self.cnt()) as usize;
-                self.data[i]
-            }
+            0 => f64::NAN,
+            _ => self.quantile.of(&self.data, p),
         }
     }
 
@@ -335,18 +559,53 @@ impl Numbers {
 
     /// median of Numbers vector of f64
     pub fn med(&self) -> f64 {
-        match self.data.len() {
-            0 => NAN,
-            1 => self.data[0],
-            2 => self.mea(),
-            _ => self.prc(0.5),
+        self.prc(0.5)
+    }
+    /// count of values with the error code label
+    pub fn code_cnt(&self, label: &str) -> f64 {
+        match self.codes.iter().find(|(l, _)| l == label) {
+            Some((_, n)) => *n,
+            None => f64::NAN,
+        }
+    }
+
+    /// count of values with any error code
+    pub fn codes_cnt(&self) -> f64 {
+        self.codes.iter().fold(0.0, |sum, (_, n)| sum + n)
+    }
+
+    /// percentage of all rows of the column not failed with an error code,
+    /// rows dropped for other reasons (missing, filtered, ..) count as not failed
+    pub fn code_yld(&self) -> f64 {
+        match self.quality.rows > 0.0 {
+            true => 100.0 * (self.quality.rows - self.codes_cnt()) / self.quality.rows,
+            false => f64::NAN,
         }
     }
+
+    /// percentage of all values of the column used for the statistics
+    pub fn completeness(&self) -> f64 {
+        let cnt = match self.cnt() {
+            c if c.is_nan() => 0.0,
+            c => c,
+        };
+        match self.quality.rows > 0.0 {
+            true => 100.0 * cnt / self.quality.rows,
+            false => f64::NAN,
+        }
+    }
+
     /// count of Numbers vector of f64
     pub fn cnt(&self) -> f64 {
+        if let Some(m) = &self.moments {
+            return match m.cnt > 0.0 {
+                true => m.cnt,
+                false => f64::NAN,
+            };
+        }
         let l = self.data.len();
         match l {
-            0 => NAN,
+            0 => f64::NAN,
             _ => l as f64,
         }
     }
@@ -380,3 +639,108 @@ impl Numbers {
         (bins, d)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// probabilities of the reference quantiles
+    const PROBS: [f64; 7] = [0.0, 0.1, 0.25, 0.5, 0.75, 0.9, 1.0];
+
+    /// R quantile(x, PROBS, type = 1..9) of samples with n = 1, 2, 5 and 10
+    #[rustfmt::skip]
+    const REFERENCE: [(&[f64], [[f64; 7]; 9]); 4] = [
+        (&[3.0], [[3.0; 7]; 9]),
+        (&[1.0, 4.0], [
+            [1.0, 1.0, 1.0, 1.0, 4.0, 4.0, 4.0],
+            [1.0, 1.0, 1.0, 2.5, 4.0, 4.0, 4.0],
+            [1.0, 1.0, 1.0, 1.0, 4.0, 4.0, 4.0],
+            [1.0, 1.0, 1.0, 1.0, 2.5, 3.4, 4.0],
+            [1.0, 1.0, 1.0, 2.5, 4.0, 4.0, 4.0],
+            [1.0, 1.0, 1.0, 2.5, 4.0, 4.0, 4.0],
+            [1.0, 1.3, 1.75, 2.5, 3.25, 3.7, 4.0],
+            [1.0, 1.0, 1.0, 2.5, 4.0, 4.0, 4.0],
+            [1.0, 1.0, 1.0, 2.5, 4.0, 4.0, 4.0],
+        ]),
+        (&[2.0, 3.0, 5.0, 7.0, 11.0], [
+            [2.0, 2.0, 3.0, 5.0, 7.0, 11.0, 11.0],
+            [2.0, 2.0, 3.0, 5.0, 7.0, 11.0, 11.0],
+            [2.0, 2.0, 2.0, 3.0, 7.0, 7.0, 11.0],
+            [2.0, 2.0, 2.25, 4.0, 6.5, 9.0, 11.0],
+            [2.0, 2.0, 2.75, 5.0, 8.0, 11.0, 11.0],
+            [2.0, 2.0, 2.5, 5.0, 9.0, 11.0, 11.0],
+            [2.0, 2.4, 3.0, 5.0, 7.0, 9.4, 11.0],
+            [2.0, 2.0, 2.6666666666666665, 5.0, 8.333333333333334, 11.0, 11.0],
+            [2.0, 2.0, 2.6875, 5.0, 8.25, 11.0, 11.0],
+        ]),
+        (&[0.5, 1.2, 2.0, 2.7, 3.0, 4.4, 5.0, 6.1, 8.0, 9.5], [
+            [0.5, 0.5, 2.0, 3.0, 6.1, 8.0, 9.5],
+            [0.5, 0.85, 2.0, 3.7, 6.1, 8.75, 9.5],
+            [0.5, 0.5, 1.2, 3.0, 6.1, 8.0, 9.5],
+            [0.5, 0.5, 1.6, 3.0, 5.55, 8.0, 9.5],
+            [0.5, 0.85, 2.0, 3.7, 6.1, 8.75, 9.5],
+            [0.5, 0.57, 1.8, 3.7, 6.575, 9.35, 9.5],
+            [0.5, 1.13, 2.175, 3.7, 5.825, 8.15, 9.5],
+            [0.5, 0.7566666666666667, 1.9333333333333333, 3.7, 6.258333333333334, 8.95, 9.5],
+            [0.5, 0.78, 1.95, 3.7, 6.21875, 8.9, 9.5],
+        ]),
+    ];
+
+    #[test]
+    fn quantile_types_as_r() {
+        for (sorted, expected) in REFERENCE.iter() {
+            for (t, row) in expected.iter().enumerate() {
+                let quantile = Quantile::from_number(t + 1).unwrap();
+                for (p, x) in PROBS.iter().zip(row.iter()) {
+                    let q = quantile.of(sorted, *p);
+                    assert!(
+                        (q - x).abs() < 1e-12,
+                        "type {} n {} p {}: {} instead of {}",
+                        t + 1,
+                        sorted.len(),
+                        p,
+                        q,
+                        x
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn quantile_out_of_range() {
+        assert!(Quantile::Type7.of(&[], 0.5).is_nan());
+        assert!(Quantile::Type7.of(&[1.0], 1.5).is_nan());
+        assert!(Quantile::Type7.of(&[1.0], -0.1).is_nan());
+        assert_eq!(Quantile::from_number(0), None);
+        assert_eq!(Quantile::from_number(10), None);
+    }
+
+    #[test]
+    fn code_yield_of_all_rows() {
+        let sentinels = [Sentinel {
+            low: 9999.0,
+            high: 9999.0,
+            label: "open".to_string(),
+        }];
+        let nums = [1.0, 2.0, 9999.0, f64::NAN, 1e12, 2.0, 9999.0, 1.5];
+        let numbers = Numbers::new(&nums, 1e9, &sentinels, &Filter::None, Quantile::default());
+        assert_eq!(numbers.cnt(), 4.0);
+        assert_eq!(numbers.code_yld(), 75.0);
+        let numbers = Numbers::new(&[], 1e9, &sentinels, &Filter::None, Quantile::default());
+        assert!(numbers.code_yld().is_nan());
+    }
+
+    #[test]
+    fn percentile_fraction_or_percent() {
+        let (sorted, expected) = REFERENCE[3];
+        let numbers = Numbers::new(sorted, f64::MAX, &[], &Filter::None, Quantile::default());
+        assert_eq!(numbers.prc(1.0), 9.5);
+        assert_eq!(numbers.prc(100.0), 9.5);
+        assert_eq!(numbers.prc(0.0), 0.5);
+        assert_eq!(numbers.prc(0.25), expected[6][2]);
+        assert_eq!(numbers.prc(25.0), expected[6][2]);
+        assert_eq!(numbers.med(), expected[6][3]);
+        assert!(numbers.prc(101.0).is_nan());
+    }
+}
@@ -153,6 +153,15 @@ impl Pdf {
         }
     }
 
+    /// continues on a new page at the top
+    pub fn add_page(&mut self) {
+        self.pos.y = 0.0;
+        let (page, layer) = self
+            .doc
+            .add_page(Mm(self.siz.wid), Mm(self.siz.hei), "layer");
+        self.lay = self.doc.get_page(page).get_layer(layer);
+    }
+
     pub fn point(&self, pos: Pos) -> Point {
         Point::new(
             Mm(pos.x + self.mar.lef),
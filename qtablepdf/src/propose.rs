@@ -0,0 +1,202 @@
+//! control limits proposed from the current data, compared with the limits applied
+
+use crate::config::QTableProps;
+use crate::derive::derive_limits;
+use crate::limits::Limits;
+use crate::numbers::{F64Ext, Numbers};
+use crate::pdf::{tint, Pdf, Tint};
+use crate::table::{CellContent, Table};
+use crate::workbook::file_path;
+use csv::Writer;
+use std::path::{Path, PathBuf};
+
+/// significant digits of the limits in the proposal file
+const PROPOSAL_DIGITS: usize = 5;
+
+/// applied and proposed control limits of a parameter
+pub struct Proposal {
+    /// column of the parameter, printed from 1 as in the results table
+    pub number: usize,
+    pub name: String,
+    pub unit: String,
+    /// limits applied to the parameter, NAN if not set
+    pub limits: Limits,
+    pub lcl: f64,
+    pub ucl: f64,
+}
+
+impl Proposal {
+    /// control limits derived from the numbers as set by OPT derive
+    pub fn new(
+        number: usize,
+        name: &str,
+        unit: &str,
+        limits: &Limits,
+        numbers: &Numbers,
+        qtableprops: &QTableProps,
+    ) -> Self {
+        let derived = derive_limits(numbers, &qtableprops.derive);
+        Proposal {
+            number,
+            name: name.to_string(),
+            unit: unit.to_string(),
+            limits: limits.clone(),
+            lcl: derived[3],
+            ucl: derived[4],
+        }
+    }
+
+    fn applied(&self, key: &str) -> f64 {
+        self.limits.get(key).copied().unwrap_or(f64::NAN)
+    }
+
+    /// true, if a proposed control limit lies outside the spec limits
+    fn outside_spec(&self) -> bool {
+        self.lcl < self.applied("lsl") || self.ucl > self.applied("usl")
+    }
+}
+
+/// change from old to new in percent of old, NAN without old
+fn change(old: f64, new: f64) -> f64 {
+    match old == 0.0 {
+        true => f64::NAN,
+        false => (new - old) / old.abs() * 100.0,
+    }
+}
+
+/// proposal file next to the first limits file, or the first data file without limits file
+pub fn proposal_path(datpaths: &[String], limpaths: &[String]) -> PathBuf {
+    let path = Path::new(file_path(limpaths.first().unwrap_or(&datpaths[0])));
+    let mut pathbuf = PathBuf::new();
+    pathbuf.push(path.parent().unwrap());
+    pathbuf.push(path.file_stem().unwrap());
+    pathbuf.set_extension("prop.csv");
+    pathbuf
+}
+
+/// writes a limits file with the proposed control limits only, the applied ones and their change appended:
+/// a diff to be given after the limits files, which keeps their spec limits, filters, KEY, VALID_FROM/VALID_TO,
+/// REVISION and CPK_MIN/SPECYLD_MIN/CTRLYLD_MIN columns and expressions
+pub fn write_proposals(proposals: &[Proposal], proppath: &Path) -> Result<(), String> {
+    let mut wtr = match Writer::from_path(proppath) {
+        Ok(wtr) => wtr,
+        Err(e) => return Err(format!("{:?}", e)),
+    };
+
+    match wtr.write_record([
+        "USE",
+        "PAR",
+        "UNIT",
+        "LSL",
+        "TGT",
+        "USL",
+        "LCL",
+        "UCL",
+        "",
+        "<FIL",
+        "TER>",
+        "",
+        "LCL_OLD",
+        "UCL_OLD",
+        "LCL_CHANGE_%",
+        "UCL_CHANGE_%",
+    ]) {
+        Ok(_) => (),
+        Err(e) => return Err(format!("{:?}", e)),
+    };
+
+    for p in proposals.iter() {
+        let frmt = |x: f64| x.frmtf64(PROPOSAL_DIGITS, "");
+        match wtr.write_record([
+            "x",
+            &p.name,
+            &p.unit,
+            "",
+            "",
+            "",
+            &frmt(p.lcl),
+            &frmt(p.ucl),
+            "",
+            "",
+            "",
+            "",
+            &frmt(p.applied("lcl")),
+            &frmt(p.applied("ucl")),
+            &change(p.applied("lcl"), p.lcl).frmtf64(3, ""),
+            &change(p.applied("ucl"), p.ucl).frmtf64(3, ""),
+        ]) {
+            Ok(_) => (),
+            Err(e) => return Err(format!("{:?}", e)),
+        };
+    }
+    match wtr.flush() {
+        Ok(_) => (),
+        Err(e) => return Err(format!("{:?}", e)),
+    };
+    Ok(())
+}
+
+/// adds pages with the applied and proposed control limits,
+/// rows with proposed control limits outside the spec limits are marked
+pub fn proposal_page(
+    pdf: &mut Pdf,
+    proposals: &[Proposal],
+    numwidth: usize,
+    qtableprops: &QTableProps,
+) {
+    pdf.add_page();
+    pdf.lay.set_fill_color(tint(&Tint::White));
+    pdf.lay.set_outline_color(tint(&Tint::Black));
+    pdf.lay.set_outline_thickness(pdf.thk);
+    pdf.pos.y += qtableprops.margin;
+
+    let headers: Vec<String> = [
+        "num",
+        "parameter",
+        "unit",
+        "lsl",
+        "usl",
+        "lcl",
+        "lcl new",
+        "lcl chg%",
+        "ucl",
+        "ucl new",
+        "ucl chg%",
+    ]
+    .iter()
+    .map(|h| h.to_string())
+    .collect();
+    let widths = vec![5., 23., 8., 8., 8., 8., 8., 7., 8., 8., 7.];
+    let caption = format!(
+        "{} control limits proposed from the data |",
+        qtableprops.caption
+    );
+    let mut table = Table::new(pdf, qtableprops.fontsize, &widths, &headers, caption.trim());
+
+    let sig = qtableprops.sig_digits;
+    let nan = &qtableprops.nanstring;
+    for p in proposals.iter() {
+        let mut line: Vec<CellContent> = vec![
+            format!("{:0width$}", p.number + 1, width = numwidth),
+            p.name.clone(),
+            p.unit.clone(),
+            p.applied("lsl").frmtf64(sig, nan),
+            p.applied("usl").frmtf64(sig, nan),
+            p.applied("lcl").frmtf64(sig, nan),
+            p.lcl.frmtf64(sig, nan),
+            change(p.applied("lcl"), p.lcl).frmtf64(3, nan),
+            p.applied("ucl").frmtf64(sig, nan),
+            p.ucl.frmtf64(sig, nan),
+            change(p.applied("ucl"), p.ucl).frmtf64(3, nan),
+        ]
+        .into_iter()
+        .map(CellContent::String)
+        .collect();
+        let color = match p.outside_spec() {
+            true => tint(&table.col.bad_color),
+            false => tint(&table.col.neutral_color),
+        };
+        table.row(&mut line, &color, false, qtableprops.captioneverypage);
+    }
+    table.table_full_line();
+}
@@ -0,0 +1,293 @@
+//! Q-DAS ASCII transfer format (.dfq, .dfd/.dfx) input of measured characteristics
+use crate::dialect::{read_to_string, Dialect};
+use std::collections::HashMap;
+use std::path::Path;
+
+/// categorical columns leading the characteristic columns, all empty ones are dropped
+pub const QDAS_KEYS: [&str; 4] = ["PART", "BATCH", "IDENT", "DATE"];
+
+/// the keys grouping the parts, categorical even with numbers only
+pub const QDAS_GROUP_KEYS: [&str; 2] = ["PART", "BATCH"];
+
+/// separates the characteristics of a value line
+const CHAR_SEPARATOR: char = '\u{0f}';
+
+/// separates value, attribute, date/time, events, batch, ... of one characteristic
+const FIELD_SEPARATOR: char = '\u{14}';
+
+/// value attribute of deleted values
+const ATTRIBUTE_DELETED: &str = "255";
+
+/// characteristic defined by K2xxx fields
+#[derive(Debug, Clone, PartialEq)]
+pub struct QdasChar {
+    pub name: String,
+    pub lsl: f64,
+    pub tgt: f64,
+    pub usl: f64,
+    pub unit: String,
+}
+
+/// measured values of a Q-DAS file, one row per measured part
+#[derive(Debug, Clone, PartialEq)]
+pub struct Qdas {
+    /// the QDAS_KEYS found with at least one value
+    pub keys: Vec<String>,
+    /// characteristics in order of their number
+    pub chars: Vec<QdasChar>,
+    /// values of the keys followed by one value per characteristic (empty if not measured)
+    pub rows: Vec<Vec<String>>,
+}
+
+/// K-fields of one characteristic
+#[derive(Debug, Default, Clone)]
+struct Fields {
+    number: Option<String>,
+    description: Option<String>,
+    lsl: Option<f64>,
+    tgt: Option<f64>,
+    usl: Option<f64>,
+    unit: Option<String>,
+}
+
+/// one measured part
+#[derive(Debug, Default, Clone)]
+struct Row {
+    batch: String,
+    ident: String,
+    date: String,
+    vals: HashMap<usize, String>,
+}
+
+/// true, if the file has a Q-DAS extension (.dfq, .dfd, .dfx)
+pub fn is_qdas(path: &str) -> bool {
+    match Path::new(path).extension() {
+        Some(ext) => {
+            let ext = ext.to_string_lossy().to_lowercase();
+            ext == "dfq" || ext == "dfd" || ext == "dfx"
+        }
+        None => false,
+    }
+}
+
+/// the description (.dfd) and value (.dfx) file belonging to each other
+fn companion(path: &str, ext: &str) -> Option<String> {
+    let p = Path::new(path).with_extension(ext);
+    if p.is_file() {
+        return Some(p.to_string_lossy().to_string());
+    }
+    let p = Path::new(path).with_extension(ext.to_uppercase());
+    if p.is_file() {
+        return Some(p.to_string_lossy().to_string());
+    }
+    None
+}
+
+/// Q-DAS number with decimal point or comma
+fn qdas_f64(s: &str) -> Option<f64> {
+    s.trim().replace(',', ".").parse::<f64>().ok()
+}
+
+/// split a K-field line like `K2110/3 9.95` into key, characteristic index (0 = all) and value
+fn kfield(line: &str) -> Option<(&str, usize, &str)> {
+    let b = line.as_bytes();
+    if b.len() < 5 || b[0] != b'K' || !b[1..5].iter().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let key = &line[..5];
+    let rest = &line[5..];
+    let (idx, value) = match rest.strip_prefix('/') {
+        Some(r) => {
+            let end = r.find(|c: char| !c.is_ascii_digit()).unwrap_or(r.len());
+            (r[..end].parse::<usize>().unwrap_or(0), &r[end..])
+        }
+        None => (1, rest),
+    };
+    Some((key, idx, value.trim()))
+}
+
+/// read the characteristics and measured values of a Q-DAS file.
+/// A .dfd description file and a .dfx value file with the same name are read together.
+pub fn read_qdas(path: &str, dialect: &Dialect) -> Result<Qdas, String> {
+    let lower = path.to_lowercase();
+    let mut files = vec![path.to_string()];
+    if lower.ends_with(".dfd") {
+        if let Some(dfx) = companion(path, "dfx") {
+            files.push(dfx);
+        }
+    }
+    if lower.ends_with(".dfx") {
+        match companion(path, "dfd") {
+            Some(dfd) => files.insert(0, dfd),
+            None => {
+                return Err(format!(
+                    "Q-DAS value file '{}' has no description file (.dfd) with the same name.",
+                    path
+                ))
+            }
+        }
+    }
+
+    let mut part = String::new();
+    let mut fields: Vec<Fields> = vec![];
+    let mut common = Fields::default();
+    let mut rows: Vec<Row> = vec![];
+    let mut row = Row::default();
+
+    for file in files.iter() {
+        let text = match read_to_string(file, dialect) {
+            Err(e) => return Err(format!("could not open Q-DAS datafile '{}': {}.", file, e)),
+            Ok(text) => text,
+        };
+
+        for line in text.lines() {
+            let line = line.trim_end_matches('\r');
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            if let Some((key, idx, value)) = kfield(line) {
+                // part fields K1xxx are indexed by part, only one part is read
+                if key.starts_with("K1") {
+                    if key == "K1001" && part.is_empty() {
+                        part = value.to_string();
+                    }
+                    continue;
+                }
+                if key == "K0100" {
+                    let n = value.parse::<usize>().unwrap_or(0);
+                    if n > fields.len() {
+                        fields.resize(n, Fields::default());
+                    }
+                    continue;
+                }
+                if idx > fields.len() {
+                    fields.resize(idx, Fields::default());
+                }
+                let f = match idx {
+                    0 => &mut common,
+                    _ => &mut fields[idx - 1],
+                };
+                match key {
+                    "K2001" => f.number = Some(value.to_string()),
+                    "K2002" => f.description = Some(value.to_string()),
+                    "K2101" => f.tgt = qdas_f64(value),
+                    "K2110" => f.lsl = qdas_f64(value),
+                    "K2111" => f.usl = qdas_f64(value),
+                    "K2142" => f.unit = Some(value.to_string()),
+                    "K0001" if idx > 0 => {
+                        // a value of an already measured characteristic starts the next part
+                        if row.vals.contains_key(&(idx - 1)) {
+                            rows.push(std::mem::take(&mut row));
+                        }
+                        row.vals.insert(idx - 1, value.replace(',', "."));
+                    }
+                    "K0002" if idx > 0 && value == ATTRIBUTE_DELETED => {
+                        row.vals.insert(idx - 1, "".to_string());
+                    }
+                    "K0004" => row.date = value.to_string(),
+                    "K0006" => row.batch = value.to_string(),
+                    "K0014" => row.ident = value.to_string(),
+                    _ => (),
+                }
+                continue;
+            }
+
+            // value line: characteristics separated by 0x0F, their fields by 0x14
+            if !row.vals.is_empty() {
+                rows.push(std::mem::take(&mut row));
+            }
+            let mut line_row = Row::default();
+            for (i, chr) in line.split(CHAR_SEPARATOR).enumerate() {
+                let f: Vec<&str> = chr.split(FIELD_SEPARATOR).collect();
+                let value = match f.get(1) {
+                    Some(attr) if attr.trim() == ATTRIBUTE_DELETED => "".to_string(),
+                    _ => f[0].trim().replace(',', "."),
+                };
+                line_row.vals.insert(i, value);
+                if line_row.date.is_empty() {
+                    line_row.date = f.get(2).unwrap_or(&"").trim().to_string();
+                }
+                if line_row.batch.is_empty() {
+                    line_row.batch = f.get(4).unwrap_or(&"").trim().to_string();
+                }
+            }
+            if line_row.vals.len() > fields.len() {
+                fields.resize(line_row.vals.len(), Fields::default());
+            }
+            rows.push(line_row);
+        }
+    }
+    if !row.vals.is_empty() {
+        rows.push(row);
+    }
+
+    if fields.is_empty() {
+        return Err(format!(
+            "no characteristics found in Q-DAS datafile '{}'.",
+            path
+        ));
+    }
+
+    let chars: Vec<QdasChar> = fields
+        .iter()
+        .enumerate()
+        .map(|(i, f)| {
+            let number = f.number.clone().or_else(|| common.number.clone());
+            let description = f.description.clone().or_else(|| common.description.clone());
+            let name = match (number, description) {
+                (Some(n), Some(d)) if !n.is_empty() && !d.is_empty() => format!("{} {}", n, d),
+                (Some(n), _) if !n.is_empty() => n,
+                (_, Some(d)) if !d.is_empty() => d,
+                _ => format!("{}", i + 1),
+            };
+            QdasChar {
+                name,
+                lsl: f.lsl.or(common.lsl).unwrap_or(f64::NAN),
+                tgt: f.tgt.or(common.tgt).unwrap_or(f64::NAN),
+                usl: f.usl.or(common.usl).unwrap_or(f64::NAN),
+                unit: f
+                    .unit
+                    .clone()
+                    .or_else(|| common.unit.clone())
+                    .unwrap_or_default(),
+            }
+        })
+        .collect();
+
+    let key_vals = |r: &Row| -> Vec<String> {
+        vec![
+            part.clone(),
+            r.batch.clone(),
+            r.ident.clone(),
+            r.date.clone(),
+        ]
+    };
+    let used: Vec<bool> = (0..QDAS_KEYS.len())
+        .map(|k| rows.iter().any(|r| !key_vals(r)[k].is_empty()))
+        .collect();
+    let keys = QDAS_KEYS
+        .iter()
+        .zip(used.iter())
+        .filter(|(_, u)| **u)
+        .map(|(k, _)| k.to_string())
+        .collect();
+
+    let rows = rows
+        .iter()
+        .map(|r| {
+            let mut vals: Vec<String> = key_vals(r)
+                .into_iter()
+                .zip(used.iter())
+                .filter(|(_, u)| **u)
+                .map(|(v, _)| v)
+                .collect();
+            for i in 0..chars.len() {
+                vals.push(r.vals.get(&i).cloned().unwrap_or_default());
+            }
+            vals
+        })
+        .collect();
+
+    Ok(Qdas { keys, chars, rows })
+}
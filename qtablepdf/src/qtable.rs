@@ -1,16 +1,126 @@
 use crate::config::QTableProps;
 use crate::data::DataTable;
 use crate::data::DataTableExt;
+use crate::data::{column_statistic, parse_datafiles, time_range, ColumnKind, Data};
 use crate::group::Groups;
 use crate::limits::LimitsTableExt;
-use crate::limits::{Limits, LimitsExt, LimitsTable, YieldOk};
+use crate::limits::{
+    check_completeness, check_numbers, common_limits, provenance_caption, threshold, worst, Limits,
+    LimitsExt, LimitsTable, Provenance, YieldOk,
+};
 use crate::numbers::{F64Ext, Numbers};
 use crate::pdf::{tint, Pdf, Pos, Tint};
+use crate::propose::{proposal_page, proposal_path, write_proposals, Proposal};
+use crate::stream::{can_stream, Stream};
 use crate::table::{CellContent, Table};
+use crate::time::{parse_time, LimitsDate, TimeWindow};
 use crate::{limits, numbers};
 use enumflags2::BitFlags;
 use printpdf::{Color, Rgb};
 
+/// marks values approximated by streaming processing
+pub const APPROX: &str = "~";
+
+/// where the statistics of the parameters come from
+pub enum Source {
+    /// all values of the data file in memory
+    Table(DataTable),
+    /// running statistics of one pass over the data file
+    Stream(Stream),
+}
+
+impl Source {
+    /// column number and name of all parameters
+    pub fn parameters(&self) -> Vec<(usize, String)> {
+        self.columns(ColumnKind::Numeric)
+    }
+
+    /// keys and names of the columns of a kind
+    pub fn columns(&self, kind: ColumnKind) -> Vec<(usize, String)> {
+        match self {
+            Source::Table(datatable) => datatable
+                .iter()
+                .filter(|(_, v)| v.kind == kind && (kind != ColumnKind::Numeric || v.selected))
+                .map(|(k, v)| (*k, v.name.clone()))
+                .collect(),
+            Source::Stream(stream) => stream
+                .names
+                .iter()
+                .zip(stream.kinds.iter())
+                .enumerate()
+                .filter(|(k, (_, c))| {
+                    **c == kind && (kind != ColumnKind::Numeric || stream.selected[*k])
+                })
+                .map(|(k, (name, _))| (k, name.clone()))
+                .collect(),
+        }
+    }
+
+    /// unit of the column k, empty if unknown
+    pub fn unit(&self, k: usize) -> String {
+        match self {
+            Source::Table(datatable) => datatable[&k].unit.clone(),
+            Source::Stream(stream) => stream.units[k].clone(),
+        }
+    }
+
+    /// statistics of the parameter in column k
+    pub fn numbers(&self, k: usize, qtableprops: &QTableProps) -> Numbers {
+        match self {
+            Source::Table(datatable) => {
+                let data = datatable.get(&k).unwrap();
+                let mut numbers = Numbers::new(
+                    &data.nums,
+                    qtableprops.float_limit,
+                    &qtableprops.sentinels,
+                    &data.filt,
+                    qtableprops.quantile,
+                );
+                let (missing, unparseable) = data.invalid_counts(None);
+                numbers.set_invalid(missing, unparseable);
+                numbers
+            }
+            Source::Stream(stream) => {
+                let mut numbers = stream.numbers(k);
+                numbers.quantile = qtableprops.quantile;
+                numbers
+            }
+        }
+    }
+
+    /// statistics of the parameter in column k within group g
+    pub fn group_numbers(
+        &self,
+        k: usize,
+        groups: &Groups,
+        g: usize,
+        qtableprops: &QTableProps,
+    ) -> Numbers {
+        match self {
+            Source::Table(datatable) => {
+                let data = datatable.get(&k).unwrap();
+                let indices = &groups.groups[g].indices;
+                let nums = data.select(indices);
+                let mut numbers = Numbers::new(
+                    &nums,
+                    qtableprops.float_limit,
+                    &qtableprops.sentinels,
+                    &data.filt,
+                    qtableprops.quantile,
+                );
+                let (missing, unparseable) = data.invalid_counts(Some(indices));
+                numbers.set_invalid(missing, unparseable);
+                numbers
+            }
+            Source::Stream(stream) => {
+                let mut numbers = stream.group_numbers(k, g);
+                numbers.quantile = qtableprops.quantile;
+                numbers
+            }
+        }
+    }
+}
+
 pub struct QTable<'a> {
     pub table: Table<'a>,
     pub qtableprops: &'a QTableProps,
@@ -22,8 +132,8 @@ impl QTable<'_> {
     ///
     /// # Arguments
     ///
-    /// * `datpath` - path to .csv data file.
-    /// * `limpath` - path to .csv limits file.
+    /// * `datpaths` - paths to .csv data files, concatenated by column names.
+    /// * `limpaths` - paths to .csv limits files, merged in order.
     /// * `mar` - top margin in mm.
     /// * `fnt` - font size in points.
     /// * `hea` - column headers as `&mut Vec<String>`.
@@ -38,8 +148,8 @@ impl QTable<'_> {
     /// If missing, only simple stats are inserted into the table.
     pub fn new<'a>(
         pdf: &mut Pdf,
-        datpath: &String,
-        limpath: &String,
+        datpaths: &[String],
+        limpaths: &[String],
         columns_in: &'a Vec<Column>,
         qtableprops: &QTableProps,
     ) -> Result<(), String> {
@@ -71,14 +181,62 @@ impl QTable<'_> {
         let column_widths = columns.iter().map(|x| *(x.column_width())).collect();
 
         let limitstable = &mut LimitsTable::new();
-        limitstable.read_limits(&limpath)?;
+        for limpath in limpaths.iter() {
+            limitstable.read_limits(limpath, qtableprops)?;
+        }
+        limitstable.resolve_targets()?;
+        let parsed = parse_datafiles(datpaths, qtableprops)?;
+        for datpath in datpaths.iter() {
+            limitstable.add_data_limits(datpath, &parsed);
+        }
+
+        let pending = limitstable.has_pending();
+        let streaming = qtableprops.streaming
+            && !pending
+            && datpaths.iter().all(|d| can_stream(d, qtableprops));
+        if qtableprops.streaming && pending {
+            println!("limits with expressions like mean(PAR) need the data in memory, it is not processed streaming.");
+        } else if qtableprops.streaming && !streaming {
+            println!(
+                "streaming processing is only possible for wide format CSV data files, '{}' is read into memory.",
+                datpaths.join("', '")
+            );
+        }
+        let source = match streaming {
+            true => Source::Stream(Stream::read(datpaths, limitstable, qtableprops)?),
+            false => {
+                let mut datatable = DataTable::new();
+                datatable.add_datafiles(datpaths, parsed, limitstable, qtableprops)?;
+                limitstable
+                    .resolve(&|stat, par| column_statistic(&datatable, stat, par, qtableprops))?;
+                Source::Table(datatable)
+            }
+        };
+
+        if streaming && limitstable.values().any(|row| !row.keyed.is_empty()) {
+            println!("limits with KEY or VALID_FROM/VALID_TO columns are checked for matching groups or now only in streaming processing.");
+        }
 
-        let mut datatable = DataTable::new();
-        datatable.add_data(&datpath, &limitstable, &qtableprops.filter)?;
+        if qtableprops.group_by.is_empty() {
+            let categorical: Vec<String> = source
+                .columns(ColumnKind::Categorical)
+                .into_iter()
+                .map(|(_, name)| name)
+                .collect();
+            if !categorical.is_empty() {
+                println!(
+                    "categorical columns usable for 'groupby': {}",
+                    categorical.join(", ")
+                );
+            }
+        }
 
         pdf.pos.y += qtableprops.margin;
 
-        let groups = Groups::new(&datatable, &qtableprops)?;
+        let groups = match &source {
+            Source::Table(datatable) => Groups::new(datatable, qtableprops)?,
+            Source::Stream(stream) => stream.groups.clone(),
+        };
 
         let mut qcaption = qtableprops.caption.clone();
         for (i, g) in groups.groups.iter().enumerate() {
@@ -88,6 +246,29 @@ impl QTable<'_> {
                 qcaption = format!["{} {} {} |", qcaption, g.name, g.group];
             }
         }
+        if !qtableprops.select.is_empty() {
+            let select: Vec<String> = qtableprops.select.iter().map(|p| p.to_string()).collect();
+            qcaption = format!["{} where {} |", qcaption, select.join(" and ")];
+        }
+        if !qtableprops.time_column.is_empty() {
+            let time_range = match &source {
+                Source::Table(datatable) => time_range(datatable, qtableprops)?,
+                Source::Stream(stream) => stream.time_range.clone(),
+            };
+            let window = TimeWindow::new(qtableprops);
+            qcaption = format!["{} time {} |", qcaption, time_range.caption(&window)];
+        }
+        let mut limits_caption = qtableprops.limits_date.caption();
+        let revisions = limitstable.revisions();
+        if !revisions.is_empty() {
+            limits_caption = format!["{} rev {}", limits_caption, revisions.join(", ")];
+        }
+        if !limits_caption.is_empty() {
+            qcaption = format!["{} limits {} |", qcaption, limits_caption.trim()];
+        }
+        if streaming {
+            qcaption = format!["{} {} approximate (streaming)", qcaption, APPROX];
+        }
 
         let mut qtable = QTable {
             table: Table::new(
@@ -104,32 +285,63 @@ impl QTable<'_> {
         let mut badspec: Vec<Par> = vec![];
         let mut badctrl: Vec<Par> = vec![];
         let mut badcpk: Vec<Par> = vec![];
+        let mut incomplete: Vec<Par> = vec![];
         let mut good: Vec<Par> = vec![];
         let mut nolimits: Vec<Par> = vec![];
+        let mut proposals: Vec<Proposal> = vec![];
 
-        let numwidth = (datatable.len() as f64).log10().abs() as usize + 1;
-
-        for (k, v) in datatable.iter() {
-            let numbers = numbers::Numbers::new(&v.vals, qtableprops.float_limit, &v.filt);
-            let (limok, limits) = limitstable.check_limits(
-                &v.name,
+        let parameters = source.parameters();
+        if qtableprops.limits_report {
+            limitstable.report_matches(&parameters);
+        }
+        // digits of the largest column number printed
+        let numwidth = parameters
+            .iter()
+            .map(|(k, _)| k + 1)
+            .max()
+            .unwrap_or(1)
+            .to_string()
+            .len();
+
+        for (k, name) in parameters.iter() {
+            let numbers = source.numbers(*k, qtableprops);
+            let (limok, limits, applied) =
+                qtable.check_par(&source, limitstable, *k, name, &numbers, None);
+            let limok = check_completeness(
+                limok,
                 &numbers,
-                qtable.qtableprops.spec_yield_limit,
-                qtable.qtableprops.ctrl_yield_limit,
-                qtable.qtableprops.cpk_limit,
+                qtable.qtableprops.completeness_limit,
                 qtable.qtableprops.mark,
             );
 
+            let unit = match source.unit(*k) {
+                u if u.is_empty() => limitstable.get_unit(name),
+                u => u,
+            };
+            if qtableprops.propose {
+                proposals.push(Proposal::new(
+                    *k,
+                    name,
+                    &unit,
+                    &limits,
+                    &numbers,
+                    qtableprops,
+                ));
+            }
             let par = Par {
                 number: *k,
                 group: "".to_string(),
-                name: v.name.clone(),
+                name: name.clone(),
+                unit,
                 limitsok: limok,
                 numbers,
                 limits,
+                applied,
             };
 
-            if qtableprops.order == Order::ByBadGood
+            if qtableprops.order == Order::ByBadGood && limok == YieldOk::Incomplete {
+                incomplete.push(par);
+            } else if qtableprops.order == Order::ByBadGood
                 && limitstable.len() > 0
                 && limok != YieldOk::Yes
             {
@@ -151,7 +363,7 @@ impl QTable<'_> {
             qtable
                 .table
                 .row(&mut line, &rowcolor, false, qtableprops.captioneverypage);
-            qtable.group_lines(par, &datatable, &groups, &limitstable, &columns);
+            qtable.group_lines(par, &source, &groups, limitstable, &columns);
         }
 
         //badctrl
@@ -161,7 +373,7 @@ impl QTable<'_> {
             qtable
                 .table
                 .row(&mut line, &rowcolor, false, qtableprops.captioneverypage);
-            qtable.group_lines(par, &datatable, &groups, &limitstable, &columns);
+            qtable.group_lines(par, &source, &groups, limitstable, &columns);
         }
 
         //badcpk
@@ -171,7 +383,17 @@ impl QTable<'_> {
             qtable
                 .table
                 .row(&mut line, &rowcolor, false, qtableprops.captioneverypage);
-            qtable.group_lines(par, &datatable, &groups, &limitstable, &columns);
+            qtable.group_lines(par, &source, &groups, limitstable, &columns);
+        }
+
+        //incomplete
+        for par in &incomplete {
+            qtable.group_ruler(&groups);
+            let (mut line, rowcolor) = qtable.qtable_line(par, &columns, numwidth);
+            qtable
+                .table
+                .row(&mut line, &rowcolor, false, qtableprops.captioneverypage);
+            qtable.group_lines(par, &source, &groups, limitstable, &columns);
         }
 
         //good
@@ -181,7 +403,7 @@ impl QTable<'_> {
             qtable
                 .table
                 .row(&mut line, &rowcolor, false, qtableprops.captioneverypage);
-            qtable.group_lines(par, &datatable, &groups, &limitstable, &columns);
+            qtable.group_lines(par, &source, &groups, limitstable, &columns);
         }
 
         //nolimits
@@ -191,12 +413,22 @@ impl QTable<'_> {
             qtable
                 .table
                 .row(&mut line, &rowcolor, false, qtableprops.captioneverypage);
-            qtable.group_lines(par, &datatable, &groups, &limitstable, &columns);
+            qtable.group_lines(par, &source, &groups, limitstable, &columns);
         }
 
         //last line of table
         qtable.table.table_full_line();
 
+        if qtableprops.propose {
+            let proppath = proposal_path(datpaths, limpaths);
+            write_proposals(&proposals, &proppath)?;
+            println!(
+                "control limits proposed from the data written to '{}'.",
+                proppath.display()
+            );
+            proposal_page(pdf, &proposals, numwidth, qtableprops);
+        }
+
         Ok(())
     }
 
@@ -210,7 +442,7 @@ impl QTable<'_> {
     pub fn group_lines<'a>(
         &mut self,
         par: &Par,
-        datatable: &DataTable,
+        source: &Source,
         by_groups: &Groups,
         limitstable: &LimitsTable,
         columns: &'a Vec<Column>,
@@ -236,21 +468,20 @@ impl QTable<'_> {
             self.table.wid[1] = 0.0;
         }
 
-        let data = &datatable.get(&par.number).unwrap().vals;
-        let filt = &datatable.get(&par.number).unwrap().filt;
-        for g in by_groups.groups.iter() {
-            let gvals = g
-                .indices
-                .iter()
-                .map(|i| data[*i].clone())
-                .collect::<Vec<_>>();
-            let numbers = numbers::Numbers::new(&gvals, self.qtableprops.float_limit, &filt);
-            let (limitsok, limits) = limitstable.check_limits(
+        for (i, g) in by_groups.groups.iter().enumerate() {
+            let numbers = source.group_numbers(par.number, by_groups, i, self.qtableprops);
+            let (limitsok, limits, applied) = self.check_par(
+                source,
+                limitstable,
+                par.number,
                 &par.name,
                 &numbers,
-                self.qtableprops.spec_yield_limit,
-                self.qtableprops.ctrl_yield_limit,
-                self.qtableprops.cpk_limit,
+                Some(i),
+            );
+            let limitsok = check_completeness(
+                limitsok,
+                &numbers,
+                self.qtableprops.completeness_limit,
                 self.qtableprops.mark,
             );
 
@@ -263,9 +494,11 @@ impl QTable<'_> {
                 number: par.number.clone(),
                 group: groupname,
                 name: par.name.clone(),
+                unit: par.unit.clone(),
                 limitsok,
                 numbers,
                 limits,
+                applied,
             };
 
             let (mut line, rowcolor) = self.qtable_line(&par, columns, 0);
@@ -284,6 +517,137 @@ impl QTable<'_> {
         }
     }
 
+    /// check the values of the parameter (within group g) against its limits,
+    /// with keyed or dated limits each subset of the rows against the limits matching its keys and timestamp,
+    /// returns the revisions and limits files of the limits applied
+    fn check_par(
+        &self,
+        source: &Source,
+        limitstable: &LimitsTable,
+        k: usize,
+        name: &str,
+        numbers: &Numbers,
+        g: Option<usize>,
+    ) -> (YieldOk, Limits, Applied) {
+        let props = self.qtableprops;
+        let check = |limits: Option<&Limits>, numbers: &Numbers| {
+            check_numbers(
+                limits,
+                numbers,
+                props.spec_yield_limit,
+                props.ctrl_yield_limit,
+                props.cpk_limit,
+                props.mark,
+            )
+        };
+        let row = match limitstable.get_row(name) {
+            Some((_, row)) if !row.keyed.is_empty() => row,
+            Some((_, row)) => {
+                let (ok, limits) = check(Some(&row.limits), numbers);
+                return (ok, limits, Applied::new(&row.revision, &row.source));
+            }
+            None => {
+                let (ok, limits) = check(None, numbers);
+                return (ok, limits, Applied::default());
+            }
+        };
+
+        // a group with the keys of keyed limits is checked against these
+        if let Some(g) = g {
+            if let Some(i) = row.keyed_index(&self.groups.groups[g].keys, None) {
+                let (limits, limitsource) = row.keyed_limits(i);
+                let (ok, limits) = check(Some(&limits), numbers);
+                return (
+                    ok,
+                    limits,
+                    Applied::new(&row.keyed[i].revision, &limitsource),
+                );
+            }
+        }
+        let datatable = match source {
+            Source::Table(datatable) => datatable,
+            Source::Stream(_) => {
+                // without all values, the limits valid now are checked
+                let pairs = g
+                    .map(|g| self.groups.groups[g].keys.clone())
+                    .unwrap_or_default();
+                let (limits, applied) =
+                    match row.keyed_index(&pairs, LimitsDate::Now.date().as_ref()) {
+                        Some(i) => {
+                            let (limits, limitsource) = row.keyed_limits(i);
+                            (limits, Applied::new(&row.keyed[i].revision, &limitsource))
+                        }
+                        None => (row.limits.clone(), Applied::new(&row.revision, &row.source)),
+                    };
+                let (ok, limits) = check(Some(&limits), numbers);
+                return (ok, limits, applied);
+            }
+        };
+
+        // key columns found in the data
+        let mut keycolumns: Vec<&Data> = vec![];
+        for (column, _) in row.keyed.iter().flat_map(|k| k.keys.iter()) {
+            if let Some(data) = datatable.values().find(|v| v.name == *column) {
+                if !keycolumns.iter().any(|d| d.name == *column) {
+                    keycolumns.push(data);
+                }
+            }
+        }
+        let timecolumn = match props.limits_date {
+            LimitsDate::Timestamp => datatable.values().find(|v| v.name == props.time_column),
+            _ => None,
+        };
+
+        // rows of each keyed limits, the last for the rows matching none
+        let data = &datatable[&k];
+        let indices: Vec<usize> = match g {
+            Some(g) => self.groups.groups[g].indices.clone(),
+            None => (0..data.len()).collect(),
+        };
+        let mut subsets: Vec<Vec<usize>> = vec![vec![]; row.keyed.len() + 1];
+        for i in indices {
+            let pairs: Vec<(String, String)> = keycolumns
+                .iter()
+                .map(|d| (d.name.clone(), d.value(i).trim().to_string()))
+                .collect();
+            let time = timecolumn.and_then(|d| parse_time(&d.value(i), &props.time_format));
+            match row.keyed_index(&pairs, time.as_ref()) {
+                Some(s) => subsets[s].push(i),
+                None => subsets[row.keyed.len()].push(i),
+            }
+        }
+
+        let mut ok = YieldOk::NoLimits;
+        let mut applied = Applied::default();
+        let mut all_limits = vec![];
+        for (s, subset) in subsets
+            .iter()
+            .enumerate()
+            .filter(|(_, subset)| !subset.is_empty())
+        {
+            let (limits, subset_applied) = match s < row.keyed.len() {
+                true => {
+                    let (limits, limitsource) = row.keyed_limits(s);
+                    (limits, Applied::new(&row.keyed[s].revision, &limitsource))
+                }
+                false => (row.limits.clone(), Applied::new(&row.revision, &row.source)),
+            };
+            let nums = data.select(subset);
+            let numbers = Numbers::new(
+                &nums,
+                props.float_limit,
+                &props.sentinels,
+                &data.filt,
+                props.quantile,
+            );
+            ok = worst(ok, check(Some(&limits), &numbers).0);
+            applied.add(&subset_applied);
+            all_limits.push(limits);
+        }
+        // only the limits applied to all subsets are shown
+        (ok, common_limits(&all_limits), applied)
+    }
+
     pub fn dimm_color(&self, color: &Color) -> Color {
         let mut color_vec = color.clone().into_vec();
         if color_vec[0] == 1.0 {
@@ -310,30 +674,22 @@ impl QTable<'_> {
         columns: &'a Vec<Column>,
         numwidth: usize,
     ) -> (Vec<CellContent<'a>>, Color) {
-        let k = par.number;
         let name = &par.name;
         let group = &par.group;
         let limok = par.limitsok;
-        let param = name.clone();
+        let unit_column = columns.iter().any(|c| matches!(c, Column::Unit(_, _)));
+        let param = match par.unit.is_empty() || unit_column || !group.is_empty() {
+            true => name.clone(),
+            false => format!("{} [{}]", name, par.unit),
+        };
         let numbers = &par.numbers;
-        let limits = &par.limits;
         let mut rowcolor = self.color_by_limits(&limok);
-        if numbers.data.len() < 1 {
+        if numbers.data.is_empty() && limok != YieldOk::Incomplete {
             rowcolor = tint(&Tint::White);
         }
         let line: Vec<CellContent> = columns
             .iter()
-            .map(|x| {
-                x.column_value(
-                    &k,
-                    &group,
-                    &param,
-                    &numbers,
-                    &limits,
-                    &self.qtableprops,
-                    numwidth,
-                )
-            })
+            .map(|x| x.column_value(par, &param, &self.qtableprops, numwidth))
             .collect();
         (line, rowcolor)
     }
@@ -344,6 +700,7 @@ impl QTable<'_> {
             YieldOk::SpecYieldNot => tint(&Tint::Red),
             YieldOk::CtrlYieldNot => tint(&Tint::YellowOrange),
             YieldOk::CpkNot => tint(&Tint::Fuchsia),
+            YieldOk::Incomplete => tint(&Tint::Plum),
             YieldOk::NoLimits => tint(&Tint::White),
         }
     }
@@ -354,9 +711,43 @@ pub struct Par {
     number: usize,
     group: String,
     name: std::string::String,
+    unit: String,
     limitsok: limits::YieldOk,
     numbers: numbers::Numbers,
     limits: std::collections::BTreeMap<std::string::String, f64>,
+    applied: Applied,
+}
+
+/// revisions and limits files of the limits applied to a parameter
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Applied {
+    pub revision: String,
+    pub source: String,
+}
+
+impl Applied {
+    pub fn new(revision: &str, source: &Provenance) -> Applied {
+        Applied {
+            revision: revision.to_string(),
+            source: provenance_caption(source),
+        }
+    }
+
+    /// add the distinct revisions and limits files of the limits of another subset
+    pub fn add(&mut self, other: &Applied) {
+        for (mine, theirs) in [
+            (&mut self.revision, &other.revision),
+            (&mut self.source, &other.source),
+        ] {
+            if theirs.is_empty() || mine.split(", ").any(|s| s == theirs) {
+                continue;
+            }
+            if !mine.is_empty() {
+                mine.push_str(", ");
+            }
+            mine.push_str(theirs);
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -375,6 +766,30 @@ pub enum Filter {
     Between(f64, f64),
 }
 
+impl Filter {
+    /// true, if a value passes a filter not depending on the other values (always for iqr, zscore)
+    pub fn keeps(&self, x: f64) -> bool {
+        match self {
+            Filter::Lower(f) => x > *f,
+            Filter::Upper(g) => x < *g,
+            Filter::Between(f, g) => x > *f && x < *g,
+            _ => true,
+        }
+    }
+
+    /// <FIL and TER> cells of the filter in a limits file
+    pub fn cells(&self) -> (String, String) {
+        match self {
+            Filter::None => ("".to_string(), "".to_string()),
+            Filter::IQR(f) => ("iqr".to_string(), f.to_string()),
+            Filter::ZScore(f) => ("zscore".to_string(), f.to_string()),
+            Filter::Lower(f) => (f.to_string(), "".to_string()),
+            Filter::Upper(g) => ("".to_string(), g.to_string()),
+            Filter::Between(f, g) => (f.to_string(), g.to_string()),
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum Align {
     SpecLimits,
@@ -397,12 +812,18 @@ pub enum Mark {
     SpecYield = 0b0001,
     ControlYield = 0b0010,
     Cpk = 0b0100,
+    Completeness = 0b1000,
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Column {
     Number(String, f64),
     Parameter(String, f64),
+    Unit(String, f64),
+    /// revisions of the limits applied
+    Revision(String, f64),
+    /// limits files of the limits applied
+    LimitsSource(String, f64),
     Count(String, f64),
     Mean(String, f64),
     Median(String, f64),
@@ -416,6 +837,24 @@ pub enum Column {
     K(String, f64),
     Cpk(String, f64),
     Cp(String, f64),
+    /// counts of all error codes
+    Codes(String, f64),
+    /// count of one error code label
+    CodeCount(String, f64, String),
+    /// percentage of values not failed with an error code
+    CodeYield(String, f64),
+    /// count of empty cells
+    Missing(String, f64),
+    /// count of cells not parseable as number
+    Unparseable(String, f64),
+    /// count of NaN values
+    NanCount(String, f64),
+    /// count of values at or above floatlimit
+    Cutoff(String, f64),
+    /// count of outliers removed by the filter
+    Filtered(String, f64),
+    /// percentage of all values used
+    Completeness(String, f64),
     Percentile(String, f64, f64),
     P25(String, f64),
     P75(String, f64),
@@ -489,6 +928,9 @@ impl Column {
         match &self {
             Column::Number(name, width) => (name, width),
             Column::Parameter(name, width) => (name, width),
+            Column::Unit(name, width) => (name, width),
+            Column::Revision(name, width) => (name, width),
+            Column::LimitsSource(name, width) => (name, width),
             Column::Count(name, width) => (name, width),
             Column::Mean(name, width) => (name, width),
             Column::Median(name, width) => (name, width),
@@ -502,6 +944,15 @@ impl Column {
             Column::K(name, width) => (name, width),
             Column::Cpk(name, width) => (name, width),
             Column::Cp(name, width) => (name, width),
+            Column::Codes(name, width) => (name, width),
+            Column::CodeCount(name, width, _) => (name, width),
+            Column::CodeYield(name, width) => (name, width),
+            Column::Missing(name, width) => (name, width),
+            Column::Unparseable(name, width) => (name, width),
+            Column::NanCount(name, width) => (name, width),
+            Column::Cutoff(name, width) => (name, width),
+            Column::Filtered(name, width) => (name, width),
+            Column::Completeness(name, width) => (name, width),
             Column::LSL(name, width) => (name, width),
             Column::TGT(name, width) => (name, width),
             Column::USL(name, width) => (name, width),
@@ -523,20 +974,28 @@ impl Column {
     }
     pub fn column_value<'a>(
         &self,
-        num: &usize,
-        group: &String,
-        par: &String,
-        numbers: &'a Numbers,
-        limits: &'a Limits,
+        par: &'a Par,
+        param: &str,
         qtableprops: &QTableProps,
         numwidth: usize,
     ) -> CellContent<'a> {
+        let num = &par.number;
+        let group = &par.group;
+        let numbers = &par.numbers;
+        let limits = &par.limits;
+        let approx = |s: String| match numbers.is_approximate() && s != qtableprops.nanstring {
+            true => format!("{}{}", APPROX, s),
+            false => s,
+        };
         match &self {
             Column::Number(_, _) => match *group != "".to_string() {
                 false => CellContent::String(format!("{:0width$}", num + 1, width = numwidth)),
                 true => CellContent::String(format!("{:}", group)),
             },
-            Column::Parameter(_, _) => CellContent::String(format!("{:}", par)),
+            Column::Parameter(_, _) => CellContent::String(param.to_string()),
+            Column::Unit(_, _) => CellContent::String(par.unit.clone()),
+            Column::Revision(_, _) => CellContent::String(par.applied.revision.clone()),
+            Column::LimitsSource(_, _) => CellContent::String(par.applied.source.clone()),
             Column::Count(_, _) => {
                 CellContent::String(numbers.cnt().frmtint(&qtableprops.nanstring))
             }
@@ -545,11 +1004,11 @@ impl Column {
                     .mea()
                     .frmtf64(qtableprops.sig_digits, &qtableprops.nanstring),
             ),
-            Column::Median(_, _) => CellContent::String(
+            Column::Median(_, _) => CellContent::String(approx(
                 numbers
                     .med()
                     .frmtf64(qtableprops.sig_digits, &qtableprops.nanstring),
-            ),
+            )),
             Column::Variance(_, _) => CellContent::String(
                 numbers
                     .var()
@@ -625,21 +1084,58 @@ impl Column {
                         .frmtf64(qtableprops.sig_digits, &qtableprops.nanstring),
                 )
             }
-            Column::Percentile(_, _, perc) => CellContent::String(
+            Column::Codes(_, _) => CellContent::String(
                 numbers
-                    .prc(*perc)
+                    .codes
+                    .iter()
+                    .filter(|(_, n)| *n > 0.0)
+                    .map(|(label, n)| format!("{} {}", label, n.frmtint(&qtableprops.nanstring)))
+                    .collect::<Vec<String>>()
+                    .join(", "),
+            ),
+            Column::CodeCount(_, _, label) => {
+                CellContent::String(numbers.code_cnt(label).frmtint(&qtableprops.nanstring))
+            }
+            Column::CodeYield(_, _) => CellContent::String(
+                numbers
+                    .code_yld()
                     .frmtf64(qtableprops.sig_digits, &qtableprops.nanstring),
             ),
-            Column::P25(_, _) => CellContent::String(
+            Column::Missing(_, _) => {
+                CellContent::String(numbers.quality.missing.frmtint(&qtableprops.nanstring))
+            }
+            Column::Unparseable(_, _) => {
+                CellContent::String(numbers.quality.unparseable.frmtint(&qtableprops.nanstring))
+            }
+            Column::NanCount(_, _) => {
+                CellContent::String(numbers.quality.nan.frmtint(&qtableprops.nanstring))
+            }
+            Column::Cutoff(_, _) => {
+                CellContent::String(numbers.quality.cutoff.frmtint(&qtableprops.nanstring))
+            }
+            Column::Filtered(_, _) => {
+                CellContent::String(numbers.quality.filtered.frmtint(&qtableprops.nanstring))
+            }
+            Column::Completeness(_, _) => CellContent::String(
                 numbers
-                    .p25()
+                    .completeness()
                     .frmtf64(qtableprops.sig_digits, &qtableprops.nanstring),
             ),
-            Column::P75(_, _) => CellContent::String(
+            Column::Percentile(_, _, perc) => CellContent::String(approx(
+                numbers
+                    .prc(*perc)
+                    .frmtf64(qtableprops.sig_digits, &qtableprops.nanstring),
+            )),
+            Column::P25(_, _) => CellContent::String(approx(
+                numbers
+                    .p25()
+                    .frmtf64(qtableprops.sig_digits, &qtableprops.nanstring),
+            )),
+            Column::P75(_, _) => CellContent::String(approx(
                 numbers
                     .p75()
                     .frmtf64(qtableprops.sig_digits, &qtableprops.nanstring),
-            ),
+            )),
             Column::LSL(_, _) => CellContent::String(limits.getstr(
                 "lsl",
                 qtableprops.sig_digits,
@@ -666,13 +1162,13 @@ impl Column {
                 &qtableprops.nanstring,
             )),
             Column::Boxplot(_, _) => {
-                CellContent::Chart(boxplot, numbers, limits, qtableprops.clone())
+                CellContent::Chart(boxplot, numbers, limits, Box::new(qtableprops.clone()))
             }
             Column::Histogram(_, _) => {
-                CellContent::Chart(histogram, numbers, limits, qtableprops.clone())
+                CellContent::Chart(histogram, numbers, limits, Box::new(qtableprops.clone()))
             }
             Column::Cpkplot(_, _) => {
-                CellContent::Chart(cpkplot, numbers, limits, qtableprops.clone())
+                CellContent::Chart(cpkplot, numbers, limits, Box::new(qtableprops.clone()))
             }
         }
     }
@@ -1166,7 +1662,8 @@ pub fn cpkplot(
         x
     };
 
-    let cpklim = x(qtableprops.cpk_limit);
+    let cpk_limit = threshold(limits, "cpk_min", qtableprops.cpk_limit);
+    let cpklim = x(cpk_limit);
 
     table.pdf.lay.set_outline_thickness(0.0);
     table.pdf.lay.set_fill_color(tint(&Tint::Plum));
@@ -1226,7 +1723,7 @@ pub fn cpkplot(
     let lsl = limits.getnum("lsl");
     let usl = limits.getnum("usl");
     let cpk = numbers.cpk(&lsl, &usl);
-    if cpk < qtableprops.cpk_limit {
+    if cpk < cpk_limit {
         table.pdf.lay.set_outline_color(tint(&Tint::Fuchsia));
     } else {
         table.pdf.lay.set_outline_color(tint(&Tint::DarkGreen));
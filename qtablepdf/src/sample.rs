@@ -46,12 +46,18 @@ pub fn help(prognam: &str) -> () {
         "USAGE:     > {} data.csv [limits.csv] [config.csv] (at least the data.csv must be provided)
         OR > {} help (to plot this help)
         OR > {} samples (to create sample_data.csv, sample_limits.csv, sample_config.csv files)
-        OR > {} data.csv limits (to derive an initial limits.csv from data.csv)
+        OR > {} data.csv [limits.csv] [config.csv] limits [out=new.lim.csv] [sigdigits=5] (to derive an initial limits.csv from data.csv)
+        OR > {} data.csv limits.csv [config.csv] lint (to check limits.csv for issues)
+        OR > {} book.xlsx (data, limits and config sheets are found by their headers)
+        OR > {} book.xlsx#Data book.xlsx#2 (sheets selected by name or number)
+        OR > {} day1.csv day2.csv 'daily_*.csv' [limits.csv] [config.csv] (data files merged by column names)
+        OR > {} data.csv global.csv product.csv local.csv (limits files merged, later ones override)
 
-         * data.csv: a CSV file in wide format containing the data
+         * data.csv: a CSV file in wide format containing the data (or a STDF V4 or Q-DAS .dfq/.dfd/.dfx file)
          * limits.csv: a CSV file containing limits, filters
-         * config.csv: a CSV file containing settings for {}",
-        prognam, prognam, prognam, prognam, prognam
+         * config.csv: a CSV file containing settings for {}
+         * book.xlsx: a .xlsx, .xlsm, .xlsb, .xls or .ods workbook instead of CSV files",
+        prognam, prognam, prognam, prognam, prognam, prognam, prognam, prognam, prognam, prognam
     )
 }
 
@@ -109,14 +115,52 @@ x,show,control,,,,
 x,show,target,,,,
 ,,,,,,
 ,,# limit for which specyield is color marked as bad if specyield is lower (default: 100.0),,,,
+,,# a SPECYLD_MIN column in the limits file overrides it per parameter,,,,
 x,specyieldlimit,100,,,,
 ,,,,,,
 ,,# limit for which ctrlyield is color marked as bad if ctrlyield is lower (default: 100.0),,,,
+,,# a CTRLYLD_MIN column in the limits file overrides it per parameter,,,,
 x,ctrlyieldlimit,100,,,,
 ,,,,,,
 ,,# limit for which cpk is color marked as bad if cpk is lower (default: 1.67),,,,
+,,# a CPK_MIN column in the limits file overrides it per parameter (e.g. 2.0 for critical characteristics),,,,
 x,cpklimit,1.67,,,,
 ,,,,,,
+,,# limit for which completeness is color marked as bad if the percentage of values used is lower (default: 90),,,,
+x,completenesslimit,90,,,,
+,,,,,,
+,,# print which limits row (exact PAR or glob / regex pattern) is used for each parameter (default: no),,,,
+x,limitsreport,no,,,,
+,limitsreport,yes,,,,
+,,,,,,
+,,# date for which limits rows with VALID_FROM/VALID_TO are chosen (default: now),,,,
+,,# now: date of the report; timestamp: timestamp of each data row (needs OPT timestamp); or a date like 2024-01-31,,,,
+x,limitsdate,now,,,,
+,limitsdate,timestamp,,,,
+,limitsdate,2024-01-31,,,,
+,,,,,,
+,,# how the limits command derives limits from the data (default: sigma 6 3),,,,
+,,# sigma: median -/+ NAM x sdev for spec and WID x sdev for control limits,,,,
+,,# percentile: spec limits at NAM and 100 - NAM percent; control limits at WID and 100 - WID percent (0 < NAM; WID < 50),,,,
+,,# tolerance: mean -/+ normal tolerance interval covering NAM percent (spec) and WID percent (control) with ARG percent confidence (all between 0 and 100),,,,
+,,# TGT is the median; bottommost checked is used,,,,
+x,derive,sigma,6,3,,
+,derive,percentile,0.135,2.275,,
+,derive,tolerance,99.73,95.45,95,
+,,,,,,
+,,# limits command: keep LSL/TGT/USL of the given limits files and derive only the control limits (default: no),,,,
+x,derivekeepspec,no,,,,
+,derivekeepspec,yes,,,,
+,,,,,,
+,,# limits command: add a row with KEY columns for every group of groupby (default: no),,,,
+x,derivegroups,no,,,,
+,derivegroups,yes,,,,
+,,,,,,
+,,# propose control limits from the data (see OPT derive) and compare them with the applied limits (default: no),,,,
+,,# writes limits.prop.csv with the proposed LCL/UCL next to the limits file and adds a PDF page with old/new values,,,,
+x,propose,no,,,,
+,propose,yes,,,,
+,,,,,,
 ,,# number of bins for histograms (default: 11),,,,
 x,histobins,11,,,,
 ,,,,,,
@@ -125,6 +169,7 @@ x,histobins,11,,,,
 x,mark,specyield,,,,
 x,mark,ctrlyield,,,,
 x,mark,cpk,,,,
+x,mark,completeness,,,,
 ,,,,,,
 ,,# string to use for empty values (default: empty string),,,,
 x,nanstring,,,,,
@@ -135,12 +180,122 @@ x,sigdigits,4,,,,
 ,,# numbers (may be reserved for  error codes) above this limit are cut off before computing stats (default: 1.79e+308),,,,
 ,floatlimit,1.00E+18,,,,
 ,,,,,,
+,,# error codes (VAL: value or range low..high; NAM: label) counted separately from the data,,,,
+,,# shown by the columns codes; codecount (ARG: label) and codeyield,,,,
+,sentinel,9.99E+37,open,,,
+,sentinel,-9.99E+37,short,,,
+,sentinel,1e30..1e36,overflow,,,
+,,,,,,
+,,# sample quantile definition (Hyndman and Fan type 1 to 9) for median; percentiles; iqr filter and boxplot (default: 7),,,,
+,,# 1: inverse of the empirical distribution; 6: Minitab and Excel PERCENTILE.EXC; 7: R and Excel PERCENTILE.INC; 8: median unbiased,,,,
+,,# bottommost checked is used,,,,
+,quantile,1,,,,
+,quantile,2,,,,
+,quantile,3,,,,
+,quantile,4,,,,
+,quantile,5,,,,
+,quantile,6,,,,
+x,quantile,7,,,,
+,quantile,8,,,,
+,quantile,9,,,,
+,,,,,,
 ,,# filter to apply to all parameters after floatlimit cutoff (default: no filter),,,,
 ,,# filters set in the limits CSV file overwrite these per parameter,,,,
 ,,# bottommost checked is used,,,,
 ,filter,iqr,1.5,,,
 ,filter,zscore,2.5,,,
 ,,,,,,
+,,# format of the CSV data file (default: wide),,,,
+,,# wide: every parameter is a column; long: one row per unit/parameter/value,,,,
+,,# bottommost checked is used,,,,
+x,datformat,wide,,,,
+,datformat,long,,,,
+,,,,,,
+,,# long format only: column containing the parameter names (default: PARAMETER),,,,
+,longparameter,PARAMETER,,,,
+,,,,,,
+,,# long format only: column containing the values (default: VALUE),,,,
+,longvalue,VALUE,,,,
+,,,,,,
+,,# long format only: columns identifying a unit (they are usable for groupby),,,,
+,longkeys,BATCH/LOT,PART,,,
+,,,,,,
+,,# how the data file is processed (default: memory),,,,
+,,# streaming reads wide format CSV data files in one pass with bounded memory:,,,,
+,,# count/mean/sdev/min/max/yields/cpk are exact; median/percentiles/histogram/boxplot are approximate and marked with ~,,,,
+,,# iqr and zscore filters and more than 1000 groups of groupby are rejected in streaming processing,,,,
+,,# bottommost checked is used,,,,
+x,processing,memory,,,,
+,processing,streaming,,,,
+,,,,,,
+,,# CSV dialect of the data and limits files (default: auto),,,,
+,,# the config file itself is always read with auto detected delimiter and encoding,,,,
+,,# bottommost checked is used,,,,
+x,delimiter,auto,,,,
+,delimiter,comma,,,,
+,delimiter,semicolon,,,,
+,delimiter,tab,,,,
+,delimiter,pipe,,,,
+,delimiter,space,,,,
+,,,,,,
+,,# quote character of the data and limits files (default: doublequote),,,,
+x,quote,doublequote,,,,
+,quote,singlequote,,,,
+,,,,,,
+,,# decimal separator (default: auto [point for comma delimited files; otherwise taken from the numbers; comma if undecided]),,,,
+x,decimal,auto,,,,
+,decimal,point,,,,
+,decimal,comma,,,,
+,,,,,,
+,,# thousands separator (default: none),,,,
+x,thousands,none,,,,
+,thousands,point,,,,
+,thousands,comma,,,,
+,thousands,space,,,,
+,thousands,apostrophe,,,,
+,,,,,,
+,,# encoding of the data and limits files (default: auto [BOM or UTF-8 or Latin-1]),,,,
+,,# other encoding labels like windows-1252 or iso-8859-15 can be used too,,,,
+x,encoding,auto,,,,
+,encoding,utf-8,,,,
+,encoding,latin1,,,,
+,encoding,utf-16le,,,,
+,encoding,utf-16be,,,,
+,,,,,,
+,,# rows selected before computation: COLUMN OP VALUE (all checked are combined by 'and'),,,,
+,,# OP: == != < <= > >= (compared as numbers if both are numbers) and ~ !~ (regex),,,,
+,select,PRODUCT/TYPE == PT1,,,,
+,select,MEAS != 0,,,,
+,select,PART ~ ^P1[0-9]$,,,,
+,,,,,,
+,,# parameters shown: name glob (e.g. VDD*) or /regex/ or column numbers of the Number column (e.g. 5-40),,,,
+,,# included by any checked include (all if none checked) and not excluded by any checked exclude,,,,
+,include,VDD*,,,,
+,include,/^I[0-9]+$/,,,,
+,include,5-40,,,,
+,exclude,*_RAW,,,,
+,,,,,,
+,,# row after the header of wide format data files holding the units of the columns (default: auto),,,,
+,,# auto: it is a units row if none of its cells is a number; a cell is above numbers and no cell is repeated in the next rows,,,,
+x,unitsrow,auto,,,,
+,unitsrow,yes,,,,
+,unitsrow,no,,,,
+,,,,,,
+,,# kind of columns (NAM and ARG: column names) overriding the detection by their values,,,,
+,,# only numeric columns are rendered as parameters,,,,
+,columnkind,numeric,MEAS,,,
+,columnkind,categorical,PART,,,
+,columnkind,timestamp,DATE,,,
+,columnkind,id,SERIAL,,,
+,,,,,,
+,,# column with date/time of the rows (VAL) and its chrono format (NAM; empty: common formats),,,,
+,,# e.g. %Y-%m-%d %H:%M:%S or %d.%m.%Y %H:%M,,,,
+,timestamp,DATE,%Y-%m-%d %H:%M:%S,,,
+,,# rows selected by time window: from/to date(time) and/or the last days before now,,,,
+,timefrom,2024-01-01,,,,
+,timeto,2024-01-31 23:59:59,,,,
+,timelastdays,7,,,,
+,,,,,,
 ,,# parameters used for grouping (column numbers starting from 1 and/or names can be mixed),,,,
 x,groupby,BATCH/LOT,3,,,
 ,,,,,,
@@ -175,6 +330,18 @@ x,column,cpkplot,cpk,5.5,,
 ,column,variance,Variance,6,,
 ,column,stddev,Sdev,6,,
 ,column,range,Range,6,,
+,column,unit,Unit,4,,
+,column,revision,Rev,3,,
+,column,limitsource,Limits from,8,,
+,column,codes,Codes,8,,
+,column,codecount,Open,3,open,
+,column,codeyield,Code yld,3,,
+,column,missing,Missing,3,,
+,column,unparseable,Unparse,3,,
+,column,nancount,NaN,3,,
+,column,cutoff,Cutoff,3,,
+,column,filtered,Filtered,3,,
+,column,completeness,Complete,3,,
 ,column,k,K,6,,
 ,column,cp,Cp,6,,
 ,column,perc,Perc 80,6,80,
@@ -0,0 +1,297 @@
+//! row selection by predicates on columns of the data, e.g. `PRODUCT/TYPE == PT1`,
+//! and parameter selection by name patterns or column numbers
+use crate::dialect::Dialect;
+use glob::Pattern;
+use regex::Regex;
+use std::cmp::Ordering;
+use std::fmt;
+
+/// comparison of a predicate, two character operators first for parsing
+const OPS: [(&str, Op); 8] = [
+    ("==", Op::Eq),
+    ("!=", Op::Ne),
+    ("<=", Op::Le),
+    (">=", Op::Ge),
+    ("!~", Op::NotMatch),
+    ("<", Op::Lt),
+    (">", Op::Gt),
+    ("~", Op::Match),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    /// regular expression matches the cell
+    Match,
+    /// regular expression does not match the cell
+    NotMatch,
+}
+
+/// `COLUMN OP VALUE`, rows are kept if the cell of the column satisfies it.
+/// Values are compared as numbers if both are numbers, otherwise as strings.
+#[derive(Debug, Clone)]
+pub struct Predicate {
+    pub column: String,
+    pub op: Op,
+    pub value: String,
+    num: Option<f64>,
+    regex: Option<Regex>,
+}
+
+impl PartialEq for Predicate {
+    fn eq(&self, other: &Predicate) -> bool {
+        self.column == other.column && self.op == other.op && self.value == other.value
+    }
+}
+
+impl fmt::Display for Predicate {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        let op = OPS.iter().find(|(_, op)| *op == self.op).unwrap().0;
+        write!(formatter, "{} {} {}", self.column, op, self.value)
+    }
+}
+
+/// number of a predicate value written in the dialect of the data files,
+/// with auto detection of the decimal separator a decimal comma is accepted too
+fn parse_number(value: &str, dialect: &Dialect) -> Option<f64> {
+    dialect.parse_f64(value).or_else(|| match dialect.decimal {
+        None => Dialect {
+            decimal: Some(','),
+            ..dialect.clone()
+        }
+        .parse_f64(value),
+        Some(_) => None,
+    })
+}
+
+/// value without one pair of enclosing quotes
+fn unquote(s: &str) -> &str {
+    for q in ['"', '\''].iter() {
+        if s.len() >= 2 && s.starts_with(*q) && s.ends_with(*q) {
+            return &s[1..s.len() - 1];
+        }
+    }
+    s
+}
+
+impl Predicate {
+    /// parse a predicate like `MEAS != 0` or `PART ~ ^P1[0-9]`, numbers written in the dialect of the data
+    pub fn parse(expr: &str, dialect: &Dialect) -> Result<Predicate, String> {
+        // the leftmost operator, two character operators win at the same position
+        let mut found: Option<(usize, &str, Op)> = None;
+        for (s, op) in OPS.iter() {
+            if let Some(pos) = expr.find(s) {
+                if found.map(|(p, _, _)| pos < p).unwrap_or(true) {
+                    found = Some((pos, s, *op));
+                }
+            }
+        }
+        let (pos, s, op) = match found {
+            Some(f) => f,
+            None => {
+                return Err(format!(
+                    "select '{}' has no operator, allowed: ==, !=, <, <=, >, >=, ~, !~.",
+                    expr
+                ))
+            }
+        };
+
+        let column = expr[..pos].trim().to_string();
+        let value = unquote(expr[pos + s.len()..].trim()).to_string();
+        if column.is_empty() {
+            return Err(format!("select '{}' has no column.", expr));
+        }
+
+        let regex = match op {
+            Op::Match | Op::NotMatch => match Regex::new(&value) {
+                Ok(r) => Some(r),
+                Err(e) => return Err(format!("invalid regex in select '{}': {}.", expr, e)),
+            },
+            _ => None,
+        };
+
+        Ok(Predicate {
+            column,
+            op,
+            num: parse_number(&value, dialect),
+            value,
+            regex,
+        })
+    }
+
+    /// true, if the cell (with its number, if parseable) satisfies the predicate
+    pub fn matches(&self, cell: &str, num: Option<f64>) -> bool {
+        let ordering = match (self.num, num) {
+            (Some(a), Some(x)) => x.partial_cmp(&a),
+            _ => Some(cell.trim().cmp(self.value.as_str())),
+        };
+        match self.op {
+            Op::Eq => ordering == Some(Ordering::Equal),
+            Op::Ne => ordering != Some(Ordering::Equal),
+            Op::Lt => ordering == Some(Ordering::Less),
+            Op::Le => matches!(ordering, Some(Ordering::Less) | Some(Ordering::Equal)),
+            Op::Gt => ordering == Some(Ordering::Greater),
+            Op::Ge => matches!(ordering, Some(Ordering::Greater) | Some(Ordering::Equal)),
+            Op::Match => self.regex.as_ref().unwrap().is_match(cell),
+            Op::NotMatch => !self.regex.as_ref().unwrap().is_match(cell),
+        }
+    }
+}
+
+/// indices (starting from 0) of the columns of the predicates in the column names of the data
+pub(crate) fn select_columns(names: &[String], select: &[Predicate]) -> Result<Vec<usize>, String> {
+    let mut columns = vec![];
+    for p in select.iter() {
+        match names.iter().position(|n| *n == p.column) {
+            Some(k) => columns.push(k),
+            None => {
+                return Err(format!(
+                    "select column '{}' not found in datafile.",
+                    p.column
+                ))
+            }
+        }
+    }
+    Ok(columns)
+}
+
+/// pattern of the parameters to include or exclude
+#[derive(Debug, Clone)]
+enum ParamMatch {
+    /// name glob, e.g. `VDD*`
+    Glob(Pattern),
+    /// name regex, written as `/regex/`
+    Regex(Regex),
+    /// column numbers as shown in the Number column, e.g. `5-40` or `7`
+    Range(usize, usize),
+}
+
+#[derive(Debug, Clone)]
+pub struct ParamPattern {
+    pub pattern: String,
+    matcher: ParamMatch,
+}
+
+impl PartialEq for ParamPattern {
+    fn eq(&self, other: &ParamPattern) -> bool {
+        self.pattern == other.pattern
+    }
+}
+
+/// column number range like `5-40` or a single number
+fn parse_range(s: &str) -> Option<(usize, usize)> {
+    let (from, to) = match s.split_once('-') {
+        Some((from, to)) => (from.trim(), to.trim()),
+        None => (s, s),
+    };
+    match (from.parse::<usize>(), to.parse::<usize>()) {
+        (Ok(from), Ok(to)) if from <= to => Some((from, to)),
+        _ => None,
+    }
+}
+
+impl ParamPattern {
+    /// parse a glob, a `/regex/` or a column range
+    pub fn parse(pattern: &str) -> Result<ParamPattern, String> {
+        let pattern = pattern.trim();
+        if pattern.is_empty() {
+            return Err("empty parameter pattern.".to_string());
+        }
+        let matcher = if pattern.len() >= 2 && pattern.starts_with('/') && pattern.ends_with('/') {
+            match Regex::new(&pattern[1..pattern.len() - 1]) {
+                Ok(r) => ParamMatch::Regex(r),
+                Err(e) => {
+                    return Err(format!(
+                        "invalid regex in parameter pattern '{}': {}.",
+                        pattern, e
+                    ))
+                }
+            }
+        } else if let Some((from, to)) = parse_range(pattern) {
+            ParamMatch::Range(from, to)
+        } else {
+            match Pattern::new(pattern) {
+                Ok(p) => ParamMatch::Glob(p),
+                Err(e) => {
+                    return Err(format!(
+                        "invalid glob in parameter pattern '{}': {}.",
+                        pattern, e
+                    ))
+                }
+            }
+        };
+        Ok(ParamPattern {
+            pattern: pattern.to_string(),
+            matcher,
+        })
+    }
+
+    /// true, if the parameter with the column number (starting from 1) matches
+    pub fn matches(&self, name: &str, number: usize) -> bool {
+        match &self.matcher {
+            ParamMatch::Glob(p) => p.matches(name),
+            ParamMatch::Regex(r) => r.is_match(name),
+            ParamMatch::Range(from, to) => number >= *from && number <= *to,
+        }
+    }
+}
+
+/// true, if the parameter is included (all without include patterns) and not excluded
+pub fn is_selected(
+    name: &str,
+    number: usize,
+    include: &[ParamPattern],
+    exclude: &[ParamPattern],
+) -> bool {
+    (include.is_empty() || include.iter().any(|p| p.matches(name, number)))
+        && !exclude.iter().any(|p| p.matches(name, number))
+}
+
+/// notice of the parameters chosen, if include or exclude patterns are set
+pub(crate) fn report_selected(
+    selected: usize,
+    total: usize,
+    include: &[ParamPattern],
+    exclude: &[ParamPattern],
+) {
+    if include.is_empty() && exclude.is_empty() {
+        return;
+    }
+    println!(
+        "{} of {} parameters selected by include/exclude.",
+        selected, total
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decimal_comma() {
+        let comma = Dialect {
+            decimal: Some(','),
+            ..Default::default()
+        };
+        let p = Predicate::parse("PARAM>1,5", &comma).unwrap();
+        assert_eq!(p.num, Some(1.5));
+        assert!(p.matches("1,6", Some(1.6)));
+        assert!(!p.matches("1,45", Some(1.45)));
+        assert!(p.matches("10", Some(10.0)));
+
+        // auto detection accepts both, a given decimal point only the point
+        let auto = Dialect::default();
+        assert_eq!(Predicate::parse("PARAM>1,5", &auto).unwrap().num, Some(1.5));
+        assert_eq!(Predicate::parse("PARAM>1.5", &auto).unwrap().num, Some(1.5));
+        let point = Dialect {
+            decimal: Some('.'),
+            ..Default::default()
+        };
+        assert_eq!(Predicate::parse("PARAM>1,5", &point).unwrap().num, None);
+    }
+}
@@ -0,0 +1,125 @@
+//! quantile sketch (merging t-digest) with bounded memory
+use std::cmp::Ordering;
+
+/// compression: about 2 * COMPRESSION centroids are kept
+const COMPRESSION: f64 = 100.0;
+
+/// values collected before they are merged into the centroids
+const BUFFER_LEN: usize = 512;
+
+/// t-digest of a stream of f64 values, NaN values are not added
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TDigest {
+    /// (mean, weight) sorted by mean
+    centroids: Vec<(f64, f64)>,
+    buffer: Vec<f64>,
+    total: f64,
+    min: f64,
+    max: f64,
+}
+
+impl TDigest {
+    pub fn new() -> Self {
+        TDigest {
+            centroids: vec![],
+            buffer: vec![],
+            total: 0.0,
+            min: f64::NAN,
+            max: f64::NAN,
+        }
+    }
+
+    pub fn add(&mut self, x: f64) {
+        if x.is_nan() {
+            return;
+        }
+        if self.total == 0.0 || x < self.min {
+            self.min = x;
+        }
+        if self.total == 0.0 || x > self.max {
+            self.max = x;
+        }
+        self.total += 1.0;
+        self.buffer.push(x);
+        if self.buffer.len() >= BUFFER_LEN {
+            self.compress();
+        }
+    }
+
+    /// merge the buffered values into the centroids
+    pub fn compress(&mut self) {
+        if self.buffer.is_empty() {
+            return;
+        }
+        let mut all: Vec<(f64, f64)> = self.centroids.drain(..).collect();
+        all.extend(self.buffer.drain(..).map(|x| (x, 1.0)));
+        all.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(Ordering::Equal));
+
+        let mut merged: Vec<(f64, f64)> = Vec::with_capacity(all.len());
+        let mut cur = all[0];
+        let mut sofar = 0.0;
+        for c in all.into_iter().skip(1) {
+            let q = (sofar + cur.1 + c.1 / 2.0) / self.total;
+            let limit = (4.0 * self.total * q * (1.0 - q) / COMPRESSION).max(1.0);
+            if cur.1 + c.1 <= limit {
+                cur.0 += (c.0 - cur.0) * c.1 / (cur.1 + c.1);
+                cur.1 += c.1;
+            } else {
+                sofar += cur.1;
+                merged.push(cur);
+                cur = c;
+            }
+        }
+        merged.push(cur);
+        self.centroids = merged;
+    }
+
+    pub fn count(&self) -> f64 {
+        self.total
+    }
+
+    /// approximate quantile q (0..1), exact min and max at the ends
+    pub fn quantile(&mut self, q: f64) -> f64 {
+        self.compress();
+        if self.centroids.is_empty() {
+            return f64::NAN;
+        }
+        if self.centroids.len() == 1 {
+            return self.centroids[0].0;
+        }
+        let target = q.clamp(0.0, 1.0) * self.total;
+
+        let first = self.centroids[0];
+        if target <= first.1 / 2.0 {
+            return self.min + (first.0 - self.min) * target / (first.1 / 2.0);
+        }
+
+        let mut cum = 0.0;
+        for w in self.centroids.windows(2) {
+            let (a, b) = (w[0], w[1]);
+            let ca = cum + a.1 / 2.0;
+            let cb = cum + a.1 + b.1 / 2.0;
+            if target < cb {
+                return a.0 + (b.0 - a.0) * (target - ca) / (cb - ca);
+            }
+            cum += a.1;
+        }
+
+        let last = self.centroids[self.centroids.len() - 1];
+        let rest = self.total - target;
+        if rest <= 0.0 {
+            return self.max;
+        }
+        self.max - (self.max - last.0) * rest / (last.1 / 2.0)
+    }
+
+    /// n values evenly spaced by rank, representing the distribution
+    pub fn sample(&mut self, n: usize) -> Vec<f64> {
+        if self.total == 0.0 || n == 0 {
+            return vec![];
+        }
+        (0..n)
+            .map(|i| self.quantile((i as f64 + 0.5) / n as f64))
+            .collect()
+    }
+}
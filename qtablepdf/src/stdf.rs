@@ -0,0 +1,442 @@
+//! STDF V4 (Standard Test Data Format) input of parametric test results
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, Read};
+
+/// categorical columns leading the parametric test columns
+pub const STDF_KEYS: [&str; 4] = ["LOT", "WAFER", "SITE", "PART"];
+
+/// record types (REC_TYP, REC_SUB) used
+const FAR: (u8, u8) = (0, 10);
+const MIR: (u8, u8) = (1, 10);
+const WIR: (u8, u8) = (2, 10);
+const PIR: (u8, u8) = (5, 10);
+const PRR: (u8, u8) = (5, 20);
+const PTR: (u8, u8) = (15, 10);
+
+/// TEST_FLG bits: RESULT not valid, test not executed
+const TEST_FLG_NO_RESULT: u8 = 0x02 | 0x10;
+
+/// OPT_FLAG bits
+const OPT_LO_LIMIT_INVALID: u8 = 0x10;
+const OPT_HI_LIMIT_INVALID: u8 = 0x20;
+const OPT_NO_LO_LIMIT: u8 = 0x40;
+const OPT_NO_HI_LIMIT: u8 = 0x80;
+
+/// parametric test found in PTR records
+#[derive(Debug, Clone, PartialEq)]
+pub struct StdfTest {
+    pub name: String,
+    pub lsl: f64,
+    pub usl: f64,
+    pub unit: String,
+}
+
+/// parametric test results of a STDF file, one row per part
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stdf {
+    /// tests in order of first appearance
+    pub tests: Vec<StdfTest>,
+    /// values of the STDF_KEYS columns followed by one value per test (empty if not tested)
+    pub rows: Vec<Vec<String>>,
+}
+
+/// a part between PIR and PRR
+struct Part {
+    lot: String,
+    wafer: String,
+    results: HashMap<usize, String>,
+}
+
+/// field reader for one record body, None if the record ends before the field
+struct Fields<'a> {
+    buf: &'a [u8],
+    pos: usize,
+    big_endian: bool,
+}
+
+impl<'a> Fields<'a> {
+    fn bytes(&mut self, n: usize) -> Option<&'a [u8]> {
+        if self.pos + n > self.buf.len() {
+            return None;
+        }
+        let b = &self.buf[self.pos..self.pos + n];
+        self.pos += n;
+        Some(b)
+    }
+    fn u1(&mut self) -> Option<u8> {
+        self.bytes(1).map(|b| b[0])
+    }
+    fn u4(&mut self) -> Option<u32> {
+        let b = self.bytes(4)?;
+        let b = [b[0], b[1], b[2], b[3]];
+        Some(match self.big_endian {
+            true => u32::from_be_bytes(b),
+            false => u32::from_le_bytes(b),
+        })
+    }
+    fn r4(&mut self) -> Option<f32> {
+        self.u4().map(f32::from_bits)
+    }
+    fn skip(&mut self, n: usize) -> Option<()> {
+        self.bytes(n).map(|_| ())
+    }
+    fn cn(&mut self) -> Option<String> {
+        let n = self.u1()? as usize;
+        let b = self.bytes(n)?;
+        Some(String::from_utf8_lossy(b).trim().to_string())
+    }
+}
+
+/// R4 value as f64 with the digits written by the tester, not the binary f32 expansion
+fn r4_to_f64(f: f32) -> f64 {
+    f.to_string().parse::<f64>().unwrap_or(f64::NAN)
+}
+
+/// byte order of a STDF file from the REC_LEN of the leading FAR record
+fn far_byte_order(head: &[u8]) -> Option<bool> {
+    if head.len() < 4 || (head[2], head[3]) != FAR {
+        return None;
+    }
+    match (head[0], head[1]) {
+        (0, 2) => Some(true),
+        (2, 0) => Some(false),
+        _ => None,
+    }
+}
+
+/// true, if the file starts with a STDF FAR record
+pub fn is_stdf(path: &str) -> bool {
+    let mut head = [0u8; 4];
+    match File::open(path) {
+        Err(_) => false,
+        Ok(mut file) => match file.read_exact(&mut head) {
+            Err(_) => false,
+            Ok(_) => far_byte_order(&head).is_some(),
+        },
+    }
+}
+
+/// read the parametric test results (PTR) of a STDF V4 file
+pub fn read_stdf(path: &str) -> Result<Stdf, String> {
+    let file = match File::open(path) {
+        Err(e) => return Err(format!("could not open STDF datafile '{}': {}.", path, e)),
+        Ok(file) => file,
+    };
+    parse_stdf(BufReader::new(file), path)
+}
+
+/// parse the records of a STDF V4 file, path is used in messages
+fn parse_stdf<R: Read>(mut rdr: R, path: &str) -> Result<Stdf, String> {
+    let mut tests: Vec<StdfTest> = vec![];
+    let mut test_index: HashMap<u32, usize> = HashMap::new();
+    let mut rows: Vec<(Vec<String>, HashMap<usize, String>)> = vec![];
+    let mut parts: HashMap<(u8, u8), Part> = HashMap::new();
+
+    let mut lot = String::new();
+    let mut wafer = String::new();
+    let mut big_endian: Option<bool> = None;
+    let mut partcount = 0;
+
+    let mut header = [0u8; 4];
+    let mut body: Vec<u8> = vec![];
+    loop {
+        if rdr.read_exact(&mut header).is_err() {
+            break;
+        }
+        let be = match big_endian {
+            Some(be) => be,
+            None => match far_byte_order(&header) {
+                Some(be) => {
+                    big_endian = Some(be);
+                    be
+                }
+                None => return Err(format!("'{}' is not a STDF V4 file.", path)),
+            },
+        };
+        let len = match be {
+            true => u16::from_be_bytes([header[0], header[1]]),
+            false => u16::from_le_bytes([header[0], header[1]]),
+        } as usize;
+        body.resize(len, 0);
+        if rdr.read_exact(&mut body).is_err() {
+            println!(
+                "STDF datafile '{}' is truncated, the last record is skipped.",
+                path
+            );
+            break;
+        }
+        let mut f = Fields {
+            buf: &body,
+            pos: 0,
+            big_endian: be,
+        };
+
+        match (header[2], header[3]) {
+            FAR => {
+                f.skip(1);
+                if let Some(ver) = f.u1() {
+                    if ver != 4 {
+                        return Err(format!(
+                            "STDF datafile '{}' has version {}, only version 4 can be read.",
+                            path, ver
+                        ));
+                    }
+                }
+            }
+            MIR => {
+                // SETUP_T, START_T, STAT_NUM, MODE_COD, RTST_COD, PROT_COD, BURN_TIM, CMOD_COD
+                f.skip(15);
+                lot = f.cn().unwrap_or_default();
+            }
+            WIR => {
+                // HEAD_NUM, SITE_GRP, START_T
+                f.skip(6);
+                wafer = f.cn().unwrap_or_default();
+            }
+            PIR => {
+                let head = f.u1().unwrap_or(0);
+                let site = f.u1().unwrap_or(0);
+                parts.insert(
+                    (head, site),
+                    Part {
+                        lot: lot.clone(),
+                        wafer: wafer.clone(),
+                        results: HashMap::new(),
+                    },
+                );
+            }
+            PTR => {
+                let num = match f.u4() {
+                    Some(num) => num,
+                    None => continue,
+                };
+                let head = f.u1().unwrap_or(0);
+                let site = f.u1().unwrap_or(0);
+                let test_flg = f.u1().unwrap_or(0);
+                f.skip(1);
+                let result = f.r4();
+                let txt = f.cn().unwrap_or_default();
+                f.cn();
+                let opt_flag = f.u1();
+                f.skip(3);
+                let lo = f.r4();
+                let hi = f.r4();
+                let units = f.cn().unwrap_or_default();
+
+                let idx = *test_index.entry(num).or_insert_with(|| {
+                    tests.push(StdfTest {
+                        name: match txt.is_empty() {
+                            true => num.to_string(),
+                            false => format!("{} {}", num, txt),
+                        },
+                        lsl: f64::NAN,
+                        usl: f64::NAN,
+                        unit: units.clone(),
+                    });
+                    tests.len() - 1
+                });
+
+                // limits of later PTRs of a test default to the first one
+                if let Some(opt) = opt_flag {
+                    let test = &mut tests[idx];
+                    if test.lsl.is_nan() && opt & (OPT_LO_LIMIT_INVALID | OPT_NO_LO_LIMIT) == 0 {
+                        test.lsl = lo.map(r4_to_f64).unwrap_or(f64::NAN);
+                    }
+                    if test.usl.is_nan() && opt & (OPT_HI_LIMIT_INVALID | OPT_NO_HI_LIMIT) == 0 {
+                        test.usl = hi.map(r4_to_f64).unwrap_or(f64::NAN);
+                    }
+                }
+
+                if test_flg & TEST_FLG_NO_RESULT != 0 {
+                    continue;
+                }
+                if let (Some(part), Some(result)) = (parts.get_mut(&(head, site)), result) {
+                    part.results.insert(idx, r4_to_f64(result).to_string());
+                }
+            }
+            PRR => {
+                let head = f.u1().unwrap_or(0);
+                let site = f.u1().unwrap_or(0);
+                // PART_FLG, NUM_TEST, HARD_BIN, SOFT_BIN, X_COORD, Y_COORD, TEST_T
+                f.skip(15);
+                let part_id = f.cn().unwrap_or_default();
+                partcount += 1;
+                if let Some(part) = parts.remove(&(head, site)) {
+                    let part_id = match part_id.is_empty() {
+                        true => partcount.to_string(),
+                        false => part_id,
+                    };
+                    rows.push((
+                        vec![part.lot, part.wafer, site.to_string(), part_id],
+                        part.results,
+                    ));
+                }
+            }
+            _ => (),
+        }
+    }
+
+    if big_endian.is_none() {
+        return Err(format!("'{}' is not a STDF V4 file.", path));
+    }
+
+    let rows = rows
+        .into_iter()
+        .map(|(mut keys, results)| {
+            for i in 0..tests.len() {
+                keys.push(results.get(&i).cloned().unwrap_or_default());
+            }
+            keys
+        })
+        .collect();
+
+    Ok(Stdf { tests, rows })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// STDF writer for fixtures, little or big endian
+    struct Writer {
+        bytes: Vec<u8>,
+        big_endian: bool,
+    }
+
+    impl Writer {
+        fn new(big_endian: bool) -> Self {
+            let mut w = Writer {
+                bytes: vec![],
+                big_endian,
+            };
+            // CPU_TYPE, STDF_VER
+            w.record(FAR, &[if big_endian { 1 } else { 2 }, 4]);
+            w
+        }
+        fn record(&mut self, typ: (u8, u8), body: &[u8]) {
+            let len = body.len() as u16;
+            match self.big_endian {
+                true => self.bytes.extend_from_slice(&len.to_be_bytes()),
+                false => self.bytes.extend_from_slice(&len.to_le_bytes()),
+            }
+            self.bytes.extend_from_slice(&[typ.0, typ.1]);
+            self.bytes.extend_from_slice(body);
+        }
+        fn u4(&self, x: u32) -> [u8; 4] {
+            match self.big_endian {
+                true => x.to_be_bytes(),
+                false => x.to_le_bytes(),
+            }
+        }
+        fn r4(&self, x: f32) -> [u8; 4] {
+            self.u4(x.to_bits())
+        }
+        fn mir(&mut self, lot: &str) {
+            let mut body = vec![0; 15];
+            body.extend(cn(lot));
+            self.record(MIR, &body);
+        }
+        fn wir(&mut self, wafer: &str) {
+            let mut body = vec![0; 6];
+            body.extend(cn(wafer));
+            self.record(WIR, &body);
+        }
+        fn pir(&mut self, site: u8) {
+            self.record(PIR, &[1, site]);
+        }
+        #[allow(clippy::too_many_arguments)]
+        fn ptr(
+            &mut self,
+            num: u32,
+            site: u8,
+            flg: u8,
+            result: f32,
+            txt: &str,
+            opt: u8,
+            lo: f32,
+            hi: f32,
+            units: &str,
+        ) {
+            let mut body = self.u4(num).to_vec();
+            body.extend([1, site, flg, 0]);
+            body.extend(self.r4(result));
+            body.extend(cn(txt));
+            body.extend(cn(""));
+            body.extend([opt, 0, 0, 0]);
+            body.extend(self.r4(lo));
+            body.extend(self.r4(hi));
+            body.extend(cn(units));
+            self.record(PTR, &body);
+        }
+        fn prr(&mut self, site: u8, part_id: &str) {
+            let mut body = vec![1, site];
+            body.extend([0; 15]);
+            body.extend(cn(part_id));
+            self.record(PRR, &body);
+        }
+    }
+
+    fn cn(s: &str) -> Vec<u8> {
+        let mut b = vec![s.len() as u8];
+        b.extend(s.as_bytes());
+        b
+    }
+
+    /// two parts tested in parallel on sites 1 and 2, test 200 not executed on site 2
+    fn fixture(big_endian: bool) -> Vec<u8> {
+        let mut w = Writer::new(big_endian);
+        w.mir("LOT7");
+        w.wir("W03");
+        w.pir(1);
+        w.pir(2);
+        w.ptr(100, 1, 0, 1.25, "VDD", 0, 1.0, 1.5, "V");
+        w.ptr(100, 2, 0, 1.3, "VDD", 0, 0.0, 9.0, "V");
+        w.ptr(200, 1, 0, 0.5, "", OPT_NO_LO_LIMIT, 0.0, 2.0, "mA");
+        w.ptr(200, 2, 0x10, 0.0, "", OPT_NO_LO_LIMIT, 0.0, 2.0, "mA");
+        w.prr(1, "P1");
+        w.prr(2, "");
+        w.bytes
+    }
+
+    #[test]
+    fn parametric_results_per_part() {
+        for big_endian in [false, true] {
+            let stdf = parse_stdf(&fixture(big_endian)[..], "fixture").unwrap();
+            assert_eq!(stdf.tests[0].name, "100 VDD");
+            assert_eq!((stdf.tests[0].lsl, stdf.tests[0].usl), (1.0, 1.5));
+            assert_eq!(stdf.tests[0].unit, "V");
+            assert_eq!(stdf.tests[1].name, "200");
+            assert!(stdf.tests[1].lsl.is_nan());
+            assert_eq!(stdf.tests[1].usl, 2.0);
+            assert_eq!(
+                stdf.rows,
+                vec![
+                    vec!["LOT7", "W03", "1", "P1", "1.25", "0.5"],
+                    vec!["LOT7", "W03", "2", "2", "1.3", ""],
+                ]
+            );
+        }
+    }
+
+    #[test]
+    fn truncated_file_keeps_complete_records() {
+        let bytes = fixture(false);
+        let stdf = parse_stdf(&bytes[..bytes.len() - 2], "fixture").unwrap();
+        assert_eq!(stdf.rows.len(), 1);
+        assert_eq!(stdf.rows[0][3], "P1");
+    }
+
+    #[test]
+    fn not_stdf_v4() {
+        let err = parse_stdf(&b"PAR,VALUE\n"[..], "data.csv").unwrap_err();
+        assert_eq!(err, "'data.csv' is not a STDF V4 file.");
+        let err = parse_stdf(&[2u8, 0, 0, 10, 2, 3][..], "v3.stdf").unwrap_err();
+        assert_eq!(
+            err,
+            "STDF datafile 'v3.stdf' has version 3, only version 4 can be read."
+        );
+        assert_eq!(far_byte_order(&[0, 2, 0, 10]), Some(true));
+        assert_eq!(far_byte_order(&[2, 0, 0, 10]), Some(false));
+    }
+}
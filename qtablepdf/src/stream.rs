@@ -0,0 +1,502 @@
+//! one pass, bounded memory processing of large wide format CSV data files
+use crate::config::QTableProps;
+use crate::data::{
+    is_units_row, merge_columns, report_missing, source_name, ColumnKind, DatFormat, SOURCE,
+    UNITS_LOOKAHEAD,
+};
+use crate::dialect::open_reader;
+use crate::group::{group_columns, group_name, Group, Groups};
+use crate::limits::{LimitsExt, LimitsTable, LimitsTableExt};
+use crate::numbers::{code_counts, code_index, Moments, Numbers, Quality, Sentinel};
+use crate::qdas::is_qdas;
+use crate::qtable::Filter;
+use crate::select::{is_selected, report_selected, select_columns};
+use crate::sketch::TDigest;
+use crate::stdf::is_stdf;
+use crate::time::{parse_time, TimeRange, TimeWindow};
+use crate::workbook::is_workbook;
+use csv::StringRecord;
+use std::collections::HashMap;
+
+/// values of the sample representing the sketched distribution of a parameter
+const SAMPLE_LEN: usize = 1000;
+
+/// groups of 'groupby' at most, each group keeps a sketch per column
+const GROUP_LIMIT: usize = 1000;
+
+/// reason a cell is not used for the statistics
+enum Reason {
+    Missing,
+    Unparseable,
+    Nan,
+    Cutoff,
+    Filtered,
+}
+
+/// a cell of a parameter as counted by the accumulator
+enum Value {
+    Number(f64),
+    /// index of the error code label
+    Code(usize),
+    Dropped(Reason),
+}
+
+/// running statistics of the values of one parameter or one group of it
+#[derive(Debug, Clone)]
+struct Accumulator {
+    cnt: f64,
+    mean: f64,
+    m2: f64,
+    min: f64,
+    max: f64,
+    /// (lower limit, upper limit, count of values within)
+    within: Vec<(f64, f64, f64)>,
+    /// count of values per error code label
+    codes: Vec<(String, f64)>,
+    /// counts of the values dropped
+    quality: Quality,
+    digest: TDigest,
+}
+
+impl Accumulator {
+    fn new(limit_pairs: &[(f64, f64)], sentinels: &[Sentinel]) -> Self {
+        Accumulator {
+            cnt: 0.0,
+            mean: 0.0,
+            m2: 0.0,
+            min: f64::NAN,
+            max: f64::NAN,
+            within: limit_pairs.iter().map(|(lo, hi)| (*lo, *hi, 0.0)).collect(),
+            codes: code_counts(sentinels),
+            quality: Quality::default(),
+            digest: TDigest::new(),
+        }
+    }
+
+    fn push(&mut self, value: &Value) {
+        self.quality.rows += 1.0;
+        match value {
+            Value::Number(x) => self.add(*x),
+            Value::Code(c) => self.codes[*c].1 += 1.0,
+            Value::Dropped(Reason::Missing) => self.quality.missing += 1.0,
+            Value::Dropped(Reason::Unparseable) => self.quality.unparseable += 1.0,
+            Value::Dropped(Reason::Nan) => self.quality.nan += 1.0,
+            Value::Dropped(Reason::Cutoff) => self.quality.cutoff += 1.0,
+            Value::Dropped(Reason::Filtered) => self.quality.filtered += 1.0,
+        }
+    }
+
+    fn add(&mut self, x: f64) {
+        self.cnt += 1.0;
+        let delta = x - self.mean;
+        self.mean += delta / self.cnt;
+        self.m2 += delta * (x - self.mean);
+        if self.cnt == 1.0 || x < self.min {
+            self.min = x;
+        }
+        if self.cnt == 1.0 || x > self.max {
+            self.max = x;
+        }
+        for (lo, hi, n) in self.within.iter_mut() {
+            if (lo.is_nan() || x >= *lo) && (hi.is_nan() || x <= *hi) {
+                *n += 1.0;
+            }
+        }
+        self.digest.add(x);
+    }
+
+    fn numbers(&self) -> Numbers {
+        if self.cnt == 0.0 {
+            let mut numbers = Numbers::from_f64(vec![]);
+            numbers.codes = self.codes.clone();
+            numbers.quality = self.quality.clone();
+            return numbers;
+        }
+        let mut digest = self.digest.clone();
+        let sample = digest.sample(SAMPLE_LEN.min(self.cnt as usize));
+        let ylds = self
+            .within
+            .iter()
+            .map(|(lo, hi, n)| match lo.is_nan() && hi.is_nan() {
+                true => (*lo, *hi, f64::NAN),
+                false => (*lo, *hi, 100.0 * n / self.cnt),
+            })
+            .collect();
+        let mut numbers = Numbers::from_moments(
+            sample,
+            Moments {
+                cnt: self.cnt,
+                mean: self.mean,
+                m2: self.m2,
+                min: self.min,
+                max: self.max,
+                ylds,
+            },
+        );
+        numbers.codes = self.codes.clone();
+        numbers.quality = self.quality.clone();
+        numbers
+    }
+}
+
+/// statistics of all columns and their groups collected in one pass over the data file
+pub struct Stream {
+    pub names: Vec<String>,
+    pub filters: Vec<Filter>,
+    pub kinds: Vec<ColumnKind>,
+    /// included and not excluded as parameter by the config
+    pub selected: Vec<bool>,
+    pub units: Vec<String>,
+    pub groups: Groups,
+    /// earliest and latest timestamp of the rows used
+    pub time_range: TimeRange,
+    columns: Vec<Accumulator>,
+    /// accumulators per group and column
+    grouped: Vec<Vec<Accumulator>>,
+}
+
+/// true, if the data file can be processed as stream
+pub fn can_stream(datpath: &str, qtableprops: &QTableProps) -> bool {
+    qtableprops.datformat == DatFormat::Wide
+        && !is_workbook(datpath)
+        && !is_stdf(datpath)
+        && !is_qdas(datpath)
+}
+
+impl Stream {
+    /// read wide format CSV data files once, keeping only running statistics and sketches.
+    /// Several files are concatenated by column names with a SOURCE column.
+    pub fn read(
+        datpaths: &[String],
+        limitstable: &LimitsTable,
+        qtableprops: &QTableProps,
+    ) -> Result<Stream, String> {
+        let mut readers = vec![];
+        let mut headers: Vec<Vec<String>> = vec![];
+        // per file the positions in the CSV record of the columns with non empty header
+        let mut positions: Vec<Vec<usize>> = vec![];
+        for datpath in datpaths.iter() {
+            let (mut rdr, dialect) = match open_reader(datpath, &qtableprops.dialect) {
+                Err(e) => return Err(format!("could not open CSV datafile '{}': {}.", datpath, e)),
+                Ok(rdr) => rdr,
+            };
+            let record = match rdr.headers() {
+                Ok(record) => record.clone(),
+                Err(e) => {
+                    return Err(format!("could not read CSV datafile '{}': {}.", datpath, e));
+                }
+            };
+            let pos: Vec<usize> = record
+                .iter()
+                .enumerate()
+                .filter(|(_, h)| !h.is_empty())
+                .map(|(i, _)| i)
+                .collect();
+            headers.push(pos.iter().map(|i| record[*i].to_string()).collect());
+            positions.push(pos);
+            readers.push((rdr, dialect));
+        }
+
+        let (mut names, mapping) = merge_columns(&headers);
+        // per file the position in the CSV record of each merged column, if any
+        let positions: Vec<Vec<Option<usize>>> = mapping
+            .iter()
+            .zip(positions.iter())
+            .map(|(columns, pos)| columns.iter().map(|c| c.map(|c| pos[c])).collect())
+            .collect();
+        let source = match datpaths.len() > 1 {
+            true => {
+                report_missing(&names, &mapping, datpaths);
+                names.push(SOURCE.to_string());
+                Some(names.len() - 1)
+            }
+            false => None,
+        };
+
+        if let Filter::IQR(_) | Filter::ZScore(_) = qtableprops.filter {
+            return Err("iqr and zscore filters need all values, the config OPT 'filter' can not be applied in streaming processing.".to_string());
+        }
+        let mut filters = vec![];
+        for name in names.iter() {
+            let filter = limitstable.get_filter(name, &qtableprops.filter);
+            if let Filter::IQR(_) | Filter::ZScore(_) = filter {
+                return Err(format!(
+                    "iqr and zscore filters need all values, the filter of '{}' in the limits file can not be applied in streaming processing.",
+                    name
+                ));
+            }
+            filters.push(filter);
+        }
+
+        let limit_pairs: Vec<Vec<(f64, f64)>> = names
+            .iter()
+            .map(|name| match limitstable.get_limits(name) {
+                Some(limits) => vec![
+                    (limits.getnum("lsl"), limits.getnum("usl")),
+                    (limits.getnum("lcl"), limits.getnum("ucl")),
+                ],
+                None => vec![],
+            })
+            .collect();
+
+        let group_cols = group_columns(&names, qtableprops)?;
+        let select_cols = select_columns(&names, &qtableprops.select)?;
+        let time_col = match qtableprops.time_column.is_empty() {
+            true => None,
+            false => match names.iter().position(|n| *n == qtableprops.time_column) {
+                Some(k) => Some(k),
+                None => {
+                    return Err(format!(
+                        "timestamp column '{}' not found in datafile.",
+                        qtableprops.time_column
+                    ))
+                }
+            },
+        };
+        let window = TimeWindow::new(qtableprops);
+        let mut time_range = TimeRange::default();
+        let mut untimed = 0;
+        // a cell not parseable as number was read
+        let mut has_text = vec![false; names.len()];
+        // all these cells were timestamps
+        let mut all_time = vec![true; names.len()];
+        let mut units = vec!["".to_string(); names.len()];
+
+        let sentinels = &qtableprops.sentinels;
+        let mut columns: Vec<Accumulator> = limit_pairs
+            .iter()
+            .map(|l| Accumulator::new(l, sentinels))
+            .collect();
+        let mut grouped: Vec<Vec<Accumulator>> = vec![];
+        let mut groups: Vec<Group> = vec![];
+        let mut group_index: HashMap<String, usize> = HashMap::new();
+
+        let mut key = String::new();
+        let mut record = StringRecord::new();
+        for ((datpath, (rdr, dialect)), positions) in datpaths
+            .iter()
+            .zip(readers.iter_mut())
+            .zip(positions.iter())
+        {
+            let source_value = source_name(datpath);
+            let value = |record: &StringRecord, k: usize| -> String {
+                match Some(k) == source {
+                    true => source_value.clone(),
+                    false => positions[k]
+                        .and_then(|i| record.get(i))
+                        .unwrap_or("")
+                        .to_string(),
+                }
+            };
+            // the first rows are read ahead to detect a units row
+            let mut ahead = vec![];
+            while ahead.len() <= UNITS_LOOKAHEAD {
+                match rdr.read_record(&mut record) {
+                    Err(e) if e.is_io_error() => {
+                        return Err(format!("could not read CSV datafile '{}': {}.", datpath, e));
+                    }
+                    Err(_) => continue,
+                    Ok(false) => break,
+                    Ok(true) if record.iter().all(|v| v.is_empty()) => continue,
+                    Ok(true) => ahead.push(record.clone()),
+                }
+            }
+            if !ahead.is_empty()
+                && is_units_row(&ahead[0], &ahead[1..], dialect, &qtableprops.units_row)
+            {
+                println!("units row found in data file '{}'.", datpath);
+                for (k, i) in positions.iter().enumerate() {
+                    let unit = i.and_then(|i| ahead[0].get(i)).unwrap_or("").trim();
+                    if units[k].is_empty() {
+                        units[k] = unit.to_string();
+                    }
+                }
+                ahead.remove(0);
+            }
+            let mut ahead = ahead.into_iter();
+            loop {
+                match ahead.next() {
+                    Some(next) => record = next,
+                    None => match rdr.read_record(&mut record) {
+                        Err(e) if e.is_io_error() => {
+                            return Err(format!(
+                                "could not read CSV datafile '{}': {}.",
+                                datpath, e
+                            ));
+                        }
+                        Err(_) => continue,
+                        Ok(false) => break,
+                        Ok(true) => (),
+                    },
+                }
+                if record.iter().all(|v| v.is_empty()) {
+                    continue;
+                }
+                let selected = qtableprops
+                    .select
+                    .iter()
+                    .zip(select_cols.iter())
+                    .all(|(p, k)| {
+                        let cell = value(&record, *k);
+                        p.matches(&cell, dialect.parse_f64(&cell))
+                    });
+                if !selected {
+                    continue;
+                }
+                if let Some(k) = time_col {
+                    match parse_time(&value(&record, k), &qtableprops.time_format) {
+                        Some(t) if window.contains(&t) => time_range.add(t),
+                        Some(_) => continue,
+                        None if window.is_unbounded() => (),
+                        None => {
+                            untimed += 1;
+                            continue;
+                        }
+                    }
+                }
+
+                let g = match group_cols.is_empty() {
+                    true => None,
+                    false => {
+                        key.clear();
+                        for (i, k) in group_cols.iter().enumerate() {
+                            if i > 0 {
+                                key.push_str(", ");
+                            }
+                            key.push_str(&names[*k]);
+                            key.push('=');
+                            key.push_str(&value(&record, *k));
+                        }
+                        Some(match group_index.get(&key) {
+                            Some(g) => *g,
+                            None if groups.len() == GROUP_LIMIT => {
+                                return Err(format!(
+                                    "more than {} groups of groupby '{}' can not be processed streaming.",
+                                    GROUP_LIMIT,
+                                    group_cols
+                                        .iter()
+                                        .map(|k| names[*k].as_str())
+                                        .collect::<Vec<&str>>()
+                                        .join("', '")
+                                ));
+                            }
+                            None => {
+                                let g = groups.len();
+                                group_index.insert(key.clone(), g);
+                                groups.push(Group {
+                                    name: group_name(g),
+                                    group: key.clone(),
+                                    keys: group_cols
+                                        .iter()
+                                        .map(|k| {
+                                            (
+                                                names[*k].clone(),
+                                                value(&record, *k).trim().to_string(),
+                                            )
+                                        })
+                                        .collect(),
+                                    indices: vec![],
+                                });
+                                grouped.push(
+                                    limit_pairs
+                                        .iter()
+                                        .map(|l| Accumulator::new(l, sentinels))
+                                        .collect(),
+                                );
+                                g
+                            }
+                        })
+                    }
+                };
+
+                for (k, i) in positions.iter().enumerate() {
+                    let cell = i.and_then(|i| record.get(i)).unwrap_or("");
+                    let value = match dialect.parse_f64(cell) {
+                        Some(x) if x.is_nan() => Value::Dropped(Reason::Nan),
+                        Some(x) => match code_index(x, sentinels, &columns[k].codes) {
+                            Some(c) => Value::Code(c),
+                            None if x >= qtableprops.float_limit => Value::Dropped(Reason::Cutoff),
+                            None if !filters[k].keeps(x) => Value::Dropped(Reason::Filtered),
+                            None => Value::Number(x),
+                        },
+                        None if cell.trim().is_empty() => Value::Dropped(Reason::Missing),
+                        None => {
+                            has_text[k] = true;
+                            if all_time[k] && parse_time(cell, "").is_none() {
+                                all_time[k] = false;
+                            }
+                            Value::Dropped(Reason::Unparseable)
+                        }
+                    };
+                    columns[k].push(&value);
+                    if let Some(g) = g {
+                        grouped[g][k].push(&value);
+                    }
+                }
+            }
+        }
+
+        if untimed > 0 {
+            println!(
+                "{} rows without valid timestamp in column '{}' are skipped.",
+                untimed, qtableprops.time_column
+            );
+        }
+
+        // without all values, ID columns are not detected
+        let kinds: Vec<ColumnKind> = names
+            .iter()
+            .enumerate()
+            .map(|(k, name)| {
+                match qtableprops
+                    .column_kinds
+                    .iter()
+                    .rev()
+                    .find(|(n, _)| n == name)
+                {
+                    Some((_, kind)) => kind.clone(),
+                    None if *name == qtableprops.time_column => ColumnKind::Timestamp,
+                    None if has_text[k] && all_time[k] => ColumnKind::Timestamp,
+                    None if has_text[k] || Some(k) == source => ColumnKind::Categorical,
+                    None => ColumnKind::Numeric,
+                }
+            })
+            .collect();
+
+        let selected: Vec<bool> = names
+            .iter()
+            .enumerate()
+            .map(|(k, name)| is_selected(name, k + 1, &qtableprops.include, &qtableprops.exclude))
+            .collect();
+        report_selected(
+            (0..names.len())
+                .filter(|k| selected[*k] && kinds[*k] == ColumnKind::Numeric)
+                .count(),
+            kinds.iter().filter(|c| **c == ColumnKind::Numeric).count(),
+            &qtableprops.include,
+            &qtableprops.exclude,
+        );
+
+        Ok(Stream {
+            kinds,
+            selected,
+            units,
+            time_range,
+            names,
+            filters,
+            groups: Groups { groups },
+            columns,
+            grouped,
+        })
+    }
+
+    /// statistics of column k
+    pub fn numbers(&self, k: usize) -> Numbers {
+        self.columns[k].numbers()
+    }
+
+    /// statistics of column k within group g
+    pub fn group_numbers(&self, k: usize, g: usize) -> Numbers {
+        self.grouped[g][k].numbers()
+    }
+}
@@ -48,7 +48,7 @@ type PlotFunc = fn(&Table, &Numbers, &Limits, &QTableProps, &Pos, f64, f64) -> (
 
 pub enum CellContent<'a> {
     String(String),
-    Chart(PlotFunc, &'a Numbers, &'a Limits, QTableProps),
+    Chart(PlotFunc, &'a Numbers, &'a Limits, Box<QTableProps>),
 }
 
 impl<'a> Table<'a> {
@@ -223,12 +223,7 @@ impl<'a> Table<'a> {
             self.table_full_line();
 
             // start new page
-            self.pdf.pos.y = 0.0;
-            let (page, layer) =
-                self.pdf
-                    .doc
-                    .add_page(Mm(self.pdf.siz.wid), Mm(self.pdf.siz.hei), "layer");
-            self.pdf.lay = self.pdf.doc.get_page(page).get_layer(layer);
+            self.pdf.add_page();
 
             self.pdf.lay.set_fill_color(tint(&self.col.fill_color));
             self.pdf
@@ -0,0 +1,155 @@
+//! timestamp column of the data and selection of rows by a time window
+use crate::config::QTableProps;
+use chrono::{DateTime, Duration, Local, NaiveDate, NaiveDateTime};
+
+/// formats tried, if no format of the timestamp column is given
+const AUTO_FORMATS: [&str; 6] = [
+    "%Y-%m-%d %H:%M:%S%.f",
+    "%Y-%m-%dT%H:%M:%S%.f",
+    "%Y-%m-%d %H:%M",
+    "%Y/%m/%d %H:%M:%S",
+    "%d.%m.%Y %H:%M:%S",
+    "%m/%d/%Y %H:%M:%S",
+];
+
+/// date formats tried (at midnight), if no format of the timestamp column is given
+const AUTO_DATE_FORMATS: [&str; 4] = ["%Y-%m-%d", "%Y/%m/%d", "%d.%m.%Y", "%m/%d/%Y"];
+
+/// format of the time range in the caption
+const CAPTION_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// parse a timestamp with a chrono format string, an empty format tries common formats
+pub fn parse_time(s: &str, format: &str) -> Option<NaiveDateTime> {
+    parse_time_or_date(s, format).map(|(t, _)| t)
+}
+
+/// parse the upper bound of a time window, a date without time is the end of that day
+pub fn parse_time_to(s: &str, format: &str) -> Option<NaiveDateTime> {
+    match parse_time_or_date(s, format)? {
+        (t, true) => t.date().and_hms_nano_opt(23, 59, 59, 999_999_999),
+        (t, false) => Some(t),
+    }
+}
+
+/// the timestamp and if it is a date only (at midnight)
+fn parse_time_or_date(s: &str, format: &str) -> Option<(NaiveDateTime, bool)> {
+    let s = s.trim();
+    if s.is_empty() {
+        return None;
+    }
+    let midnight = |d: NaiveDate| d.and_hms_opt(0, 0, 0).map(|t| (t, true));
+    if !format.is_empty() {
+        return match NaiveDateTime::parse_from_str(s, format) {
+            Ok(t) => Some((t, false)),
+            Err(_) => NaiveDate::parse_from_str(s, format).ok().and_then(midnight),
+        };
+    }
+    if let Ok(t) = DateTime::parse_from_rfc3339(s) {
+        return Some((t.naive_local(), false));
+    }
+    for f in AUTO_FORMATS.iter() {
+        if let Ok(t) = NaiveDateTime::parse_from_str(s, f) {
+            return Some((t, false));
+        }
+    }
+    for f in AUTO_DATE_FORMATS.iter() {
+        if let Ok(d) = NaiveDate::parse_from_str(s, f) {
+            return midnight(d);
+        }
+    }
+    None
+}
+
+/// date for which the limits are chosen by their VALID_FROM and VALID_TO
+#[derive(Debug, Clone, PartialEq)]
+pub enum LimitsDate {
+    /// date of the report
+    Now,
+    At(NaiveDateTime),
+    /// timestamp of each data row
+    Timestamp,
+}
+
+impl LimitsDate {
+    /// the date of all rows, none if chosen per row
+    pub fn date(&self) -> Option<NaiveDateTime> {
+        match self {
+            LimitsDate::Now => Some(Local::now().naive_local()),
+            LimitsDate::At(t) => Some(*t),
+            LimitsDate::Timestamp => None,
+        }
+    }
+
+    /// date of the limits for the caption, empty for the report date
+    pub fn caption(&self) -> String {
+        match self {
+            LimitsDate::Now => "".to_string(),
+            LimitsDate::At(t) => format!("valid {}", t.format(CAPTION_FORMAT)),
+            LimitsDate::Timestamp => "valid by timestamp".to_string(),
+        }
+    }
+}
+
+/// the time window of the rows to select, bounds are inclusive
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TimeWindow {
+    pub from: Option<NaiveDateTime>,
+    pub to: Option<NaiveDateTime>,
+}
+
+impl TimeWindow {
+    /// window from the config, 'timelastdays' counts back from now and narrows 'timefrom'
+    pub fn new(qtableprops: &QTableProps) -> TimeWindow {
+        let mut from = qtableprops.time_from;
+        if let Some(days) = qtableprops.time_last_days {
+            let start = Local::now().naive_local() - Duration::seconds((days * 86400.0) as i64);
+            from = Some(match from {
+                Some(f) if f > start => f,
+                _ => start,
+            });
+        }
+        TimeWindow {
+            from,
+            to: qtableprops.time_to,
+        }
+    }
+
+    pub fn is_unbounded(&self) -> bool {
+        self.from.is_none() && self.to.is_none()
+    }
+
+    pub fn contains(&self, t: &NaiveDateTime) -> bool {
+        self.from.map(|f| *t >= f).unwrap_or(true) && self.to.map(|u| *t <= u).unwrap_or(true)
+    }
+}
+
+/// earliest and latest timestamp seen
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TimeRange {
+    pub first: Option<NaiveDateTime>,
+    pub last: Option<NaiveDateTime>,
+}
+
+impl TimeRange {
+    pub fn add(&mut self, t: NaiveDateTime) {
+        if self.first.map(|f| t < f).unwrap_or(true) {
+            self.first = Some(t);
+        }
+        if self.last.map(|l| t > l).unwrap_or(true) {
+            self.last = Some(t);
+        }
+    }
+
+    /// time range for the caption, the bounds of the window where set, else of the data
+    pub fn caption(&self, window: &TimeWindow) -> String {
+        let fmt = |t: Option<NaiveDateTime>| match t {
+            Some(t) => t.format(CAPTION_FORMAT).to_string(),
+            None => "-".to_string(),
+        };
+        format!(
+            "{} .. {}",
+            fmt(window.from.or(self.first)),
+            fmt(window.to.or(self.last))
+        )
+    }
+}
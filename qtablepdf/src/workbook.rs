@@ -0,0 +1,118 @@
+//! spreadsheet workbook (.xlsx, .xlsm, .xlsb, .xls, .ods) input
+extern crate calamine;
+extern crate csv;
+
+use calamine::{open_workbook_auto, Data, Reader};
+use csv::Writer;
+use std::path::Path;
+
+/// file extensions read as workbook instead of CSV
+const WORKBOOK_EXTENSIONS: [&str; 5] = ["xlsx", "xlsm", "xlsb", "xls", "ods"];
+
+/// separates the workbook file from the sheet in a path like `book.xlsx#Limits` or `book.xlsx#2`
+pub const SHEET_SEPARATOR: char = '#';
+
+/// true, if the file (without sheet) has a workbook extension
+pub fn is_workbook(path: &str) -> bool {
+    let (file, _) = split_sheet(path);
+    match Path::new(file).extension() {
+        Some(ext) => {
+            let ext = ext.to_string_lossy().to_lowercase();
+            WORKBOOK_EXTENSIONS.contains(&ext.as_str())
+        }
+        None => false,
+    }
+}
+
+/// split `book.xlsx#Sheet` into file and sheet, paths without workbook extension are not split
+pub fn split_sheet(path: &str) -> (&str, Option<&str>) {
+    if let Some(i) = path.rfind(SHEET_SEPARATOR) {
+        let file = &path[..i];
+        if let Some(ext) = Path::new(file).extension() {
+            let ext = ext.to_string_lossy().to_lowercase();
+            if WORKBOOK_EXTENSIONS.contains(&ext.as_str()) {
+                return (file, Some(&path[i + 1..]));
+            }
+        }
+    }
+    (path, None)
+}
+
+/// the file part of a path, i.e. without the sheet of a workbook
+pub fn file_path(path: &str) -> &str {
+    split_sheet(path).0
+}
+
+/// names of all sheets of a workbook in workbook order
+pub fn sheet_names(path: &str) -> Result<Vec<String>, String> {
+    let (file, _) = split_sheet(path);
+    match open_workbook_auto(file) {
+        Err(e) => Err(format!("{}", e)),
+        Ok(workbook) => Ok(workbook.sheet_names()),
+    }
+}
+
+/// read one sheet of a workbook and write it as CSV into memory.
+/// The sheet is selected by name or by number (starting from 1), default is the first sheet.
+pub fn sheet_to_csv(path: &str) -> Result<Vec<u8>, String> {
+    let (file, sheet) = split_sheet(path);
+    let mut workbook = match open_workbook_auto(file) {
+        Err(e) => return Err(format!("{}", e)),
+        Ok(workbook) => workbook,
+    };
+
+    let names = workbook.sheet_names();
+    let name = match sheet {
+        None | Some("") => match names.first() {
+            Some(name) => name.clone(),
+            None => return Err(format!("workbook '{}' has no sheets", file)),
+        },
+        Some(s) => match names.iter().find(|n| n.as_str() == s) {
+            Some(name) => name.clone(),
+            None => match s.parse::<usize>() {
+                Ok(n) if n >= 1 && n <= names.len() => names[n - 1].clone(),
+                _ => {
+                    return Err(format!(
+                        "sheet '{}' not found in workbook '{}', available sheets: {}",
+                        s,
+                        file,
+                        names.join(", ")
+                    ))
+                }
+            },
+        },
+    };
+
+    let range = match workbook.worksheet_range(&name) {
+        Err(e) => return Err(format!("{}", e)),
+        Ok(range) => range,
+    };
+
+    let mut wtr = Writer::from_writer(vec![]);
+    for row in range.rows() {
+        let record: Vec<String> = row.iter().map(cell_string).collect();
+        if let Err(e) = wtr.write_record(&record) {
+            return Err(format!("{}", e));
+        }
+    }
+    match wtr.into_inner() {
+        Err(e) => Err(format!("{}", e)),
+        Ok(bytes) => Ok(bytes),
+    }
+}
+
+/// cell value as written in a CSV file with decimal point, errors become empty values
+fn cell_string(cell: &Data) -> String {
+    match cell {
+        Data::Empty | Data::Error(_) => "".to_string(),
+        Data::String(s) => s.clone(),
+        Data::Float(f) => f.to_string(),
+        Data::Int(i) => i.to_string(),
+        Data::Bool(b) => b.to_string(),
+        Data::DateTime(d) => match d.as_datetime() {
+            Some(dt) => dt.format("%Y-%m-%d %H:%M:%S").to_string(),
+            None => d.as_f64().to_string(),
+        },
+        Data::DateTimeIso(s) | Data::DurationIso(s) => s.clone(),
+    }
+}
@@ -5,6 +5,7 @@ use qtablepdf::config::{check_infiles, info, read_config};
 use qtablepdf::pdf::Pdf;
 use qtablepdf::qtable::QTable;
 use qtablepdf::sample;
+use qtablepdf::workbook::file_path;
 use std::path::Path;
 use std::{env, time::Instant};
 
@@ -45,12 +46,16 @@ fn run_app(prognam: &str, infiles: Vec<String>) -> Result<(), String> {
     let start = Instant::now();
 
     // check infiles, return error, if something goes wrong. At least a data file must be provided
-    let (datpath, limpath, cfgpath) = check_infiles(infiles, &prognam)?;
+    let (datpaths, limpaths, cfgpath) = check_infiles(infiles, &prognam)?;
 
     // info for the user, which files are used
     println!("starting .....: {}", prognam);
-    println!("using dat file: {}", datpath);
-    println!("using lim file: {}", limpath);
+    for datpath in datpaths.iter() {
+        println!("using dat file: {}", datpath);
+    }
+    for limpath in limpaths.iter() {
+        println!("using lim file: {}", limpath);
+    }
     println!("using cfg file: {}", cfgpath);
 
     // read qtableprops and columns from config file, if any, otherwise use defaults
@@ -60,10 +65,10 @@ fn run_app(prognam: &str, infiles: Vec<String>) -> Result<(), String> {
     let mut pdf = Pdf::new(&qtableprops.paper);
 
     // create the qtable
-    QTable::new(&mut pdf, &datpath, &limpath, &columns, &qtableprops)?;
+    QTable::new(&mut pdf, &datpaths, &limpaths, &columns, &qtableprops)?;
 
     // get output folder from config or data file
-    let path = Path::new(&datpath);
+    let path = Path::new(file_path(&datpaths[0]));
     let pdffolder = match qtableprops.pdffolder.is_empty() {
         true => path.parent().unwrap().to_str().unwrap(),
         false => &qtableprops.pdffolder,